@@ -3,9 +3,9 @@ use crossterm::{
     event::{self, KeyCode::*, KeyEvent, KeyEventKind},
     terminal::{self, ClearType},
 };
-use rand::random_range;
 use tetris_core_rs::{
-    AttackedLine, Cell, DEFAULT_BOARD_SIZE, MovementCommand, TetrisConfig, TetrisManager,
+    Cell, DEFAULT_BOARD_SIZE, GarbageGenerator, HoleRule, MovementCommand, TetrisConfig,
+    TetrisManager,
 };
 fn cast_to_readable(f: &Vec<Vec<Cell>>) -> String {
     let mut a = String::new();
@@ -56,6 +56,8 @@ fn main() {
         DEFAULT_BOARD_SIZE.0,
         DEFAULT_BOARD_SIZE.1,
     );
+    let mut garbage_generator =
+        GarbageGenerator::new(&0, DEFAULT_BOARD_SIZE.0, HoleRule::Clean).unwrap();
     use std::io::{self, Write};
     let mut stdout = io::stdout();
     stdout.execute(terminal::Clear(ClearType::All)).unwrap();
@@ -86,10 +88,9 @@ fn main() {
                     Down => MovementCommand::Down,
                     Char(' ') => MovementCommand::HardDrop,
                     Char('r') => {
-                        tetris_manager.command(MovementCommand::Attacked(AttackedLine {
-                            hole_indexes: Some([random_range(0..DEFAULT_BOARD_SIZE.0)].to_vec()),
-                            can_be_cleared: true,
-                        }));
+                        for attacked_line in garbage_generator.generate(1) {
+                            tetris_manager.command(MovementCommand::Attacked(attacked_line));
+                        }
                         continue;
                     }
                     _ => {
@@ -0,0 +1,279 @@
+use crate::{LineClear, MinoType, SpinKind};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub trait AttackTable {
+    fn attack(&mut self, line_clear: &LineClear) -> Attack;
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClearKind {
+    None,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    SpinMini(usize), //消去したライン数
+    Spin(usize),
+}
+impl From<&LineClear> for ClearKind {
+    fn from(line_clear: &LineClear) -> Self {
+        match (line_clear.spin, line_clear.cleared_line_count) {
+            (SpinKind::Mini, lines) => Self::SpinMini(lines),
+            (SpinKind::Full, lines) => Self::Spin(lines),
+            (SpinKind::None, 0) => Self::None,
+            (SpinKind::None, 1) => Self::Single,
+            (SpinKind::None, 2) => Self::Double,
+            (SpinKind::None, 3) => Self::Triple,
+            (SpinKind::None, _) => Self::Tetris,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Attack {
+    pub lines: usize, //送るライン数の合計
+    pub clear_kind: ClearKind,
+    pub mino_type: MinoType,
+    pub b2b: usize,   //B2Bの継続数(0ならB2Bではない)
+    pub combo: usize, //REN数(最初のライン消去は0)
+    pub is_perfect: bool,
+    pub surge: usize, //linesのうちsurgeによるもの
+}
+impl Attack {
+    fn new(line_clear: &LineClear, lines: usize) -> Self {
+        Self {
+            lines,
+            clear_kind: line_clear.into(),
+            mino_type: line_clear.mino_type,
+            b2b: b2b_chain(line_clear),
+            combo: line_clear.combo.saturating_sub(1),
+            is_perfect: line_clear.is_perfect && line_clear.cleared_line_count > 0,
+            surge: 0,
+        }
+    }
+}
+fn b2b_chain(line_clear: &LineClear) -> usize {
+    if line_clear.is_difficult() {
+        line_clear.b2b.saturating_sub(1)
+    } else {
+        0
+    }
+}
+fn base_attack(clear_kind: ClearKind) -> usize {
+    match clear_kind {
+        ClearKind::None | ClearKind::Single => 0,
+        ClearKind::Double => 1,
+        ClearKind::Triple => 2,
+        ClearKind::Tetris => 4,
+        ClearKind::SpinMini(lines) => lines.saturating_sub(1),
+        ClearKind::Spin(lines) => lines * 2,
+    }
+}
+fn guideline_attack(line_clear: &LineClear, combo_table: &[usize], perfect_clear: usize) -> Attack {
+    let mut attack = Attack::new(line_clear, 0);
+    if line_clear.cleared_line_count == 0 {
+        return attack;
+    }
+    attack.lines = base_attack(attack.clear_kind);
+    if attack.b2b > 0 {
+        attack.lines += 1;
+    }
+    attack.lines += combo_table[attack.combo.min(combo_table.len() - 1)];
+    if attack.is_perfect {
+        attack.lines += perfect_clear;
+    }
+    attack
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct PuyoPuyoTetrisAttack;
+impl AttackTable for PuyoPuyoTetrisAttack {
+    fn attack(&mut self, line_clear: &LineClear) -> Attack {
+        guideline_attack(line_clear, &[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5], 10)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Tetris99Attack;
+impl AttackTable for Tetris99Attack {
+    fn attack(&mut self, line_clear: &LineClear) -> Attack {
+        guideline_attack(line_clear, &[0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5], 10)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TetrioAttack {
+    pub b2b_chaining: bool,             //B2Bの継続数に応じてボーナスが増える
+    pub surge_threshold: Option<usize>, //この数以上続いたB2Bが途切れたときに継続数分を送る
+    pub perfect_clear: usize,
+    b2b: usize, //直前のライン消去時のLineClear::b2b
+}
+impl TetrioAttack {
+    pub fn season1() -> Self {
+        Self {
+            b2b_chaining: true,
+            surge_threshold: None,
+            perfect_clear: 10,
+            b2b: 0,
+        }
+    }
+    pub fn season2() -> Self {
+        Self {
+            b2b_chaining: false,
+            surge_threshold: Some(4),
+            perfect_clear: 5,
+            b2b: 0,
+        }
+    }
+}
+impl Default for TetrioAttack {
+    fn default() -> Self {
+        Self::season2()
+    }
+}
+impl AttackTable for TetrioAttack {
+    fn attack(&mut self, line_clear: &LineClear) -> Attack {
+        let mut attack = Attack::new(line_clear, 0);
+        if line_clear.cleared_line_count == 0 {
+            return attack;
+        }
+        let mut garbage = match attack.clear_kind {
+            ClearKind::Spin(lines) if lines >= 4 => (lines * 2 + 2) as f64,
+            clear_kind => base_attack(clear_kind) as f64,
+        };
+        if attack.b2b > 0 {
+            if self.b2b_chaining {
+                let chain = libm::log1p(attack.b2b as f64 * 0.8);
+                garbage += libm::floor(1.0 + chain);
+                if attack.b2b > 1 {
+                    garbage += (1.0 + chain % 1.0) / 3.0;
+                }
+            } else {
+                garbage += 1.0;
+            }
+        }
+        if attack.combo > 0 {
+            let combo = attack.combo as f64;
+            garbage *= 1.0 + 0.25 * combo;
+            if attack.combo > 1 {
+                garbage = garbage.max(libm::log1p(1.25 * combo));
+            }
+        }
+        attack.lines = libm::floor(garbage) as usize;
+        if attack.is_perfect {
+            attack.lines += self.perfect_clear;
+        }
+        let previous_b2b = self.b2b.saturating_sub(1);
+        if let Some(surge_threshold) = self.surge_threshold
+            && !line_clear.is_difficult()
+            && previous_b2b >= surge_threshold
+        {
+            attack.surge = previous_b2b;
+            attack.lines += previous_b2b;
+        }
+        self.b2b = line_clear.b2b;
+        attack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_clear;
+
+    fn lines(attack_table: &mut impl AttackTable, line_clear: LineClear) -> usize {
+        attack_table.attack(&line_clear).lines
+    }
+
+    #[test]
+    fn puyo_puyo_tetris_base_attacks() {
+        let mut table = PuyoPuyoTetrisAttack;
+        assert_eq!(lines(&mut table, line_clear(0, SpinKind::Full, 0, 0)), 0);
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::None, 1, 0)), 0);
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::None, 1, 0)), 1);
+        assert_eq!(lines(&mut table, line_clear(3, SpinKind::None, 1, 0)), 2);
+        assert_eq!(lines(&mut table, line_clear(4, SpinKind::None, 1, 1)), 4);
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::Mini, 1, 1)), 0);
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::Mini, 1, 1)), 1);
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::Full, 1, 1)), 2);
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::Full, 1, 1)), 4);
+        assert_eq!(lines(&mut table, line_clear(3, SpinKind::Full, 1, 1)), 6);
+    }
+
+    #[test]
+    fn guideline_b2b_combo_and_perfect_clear() {
+        let mut table = PuyoPuyoTetrisAttack;
+        let attack = table.attack(&line_clear(4, SpinKind::None, 1, 2));
+        assert_eq!((attack.lines, attack.b2b, attack.combo), (5, 1, 0));
+        assert_eq!(attack.clear_kind, ClearKind::Tetris);
+        //REN数は最初のライン消去を0として数える
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::None, 2, 0)), 1);
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::None, 5, 0)), 3);
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::None, 30, 0)), 5);
+        let perfect = LineClear {
+            is_perfect: true,
+            ..line_clear(1, SpinKind::None, 1, 0)
+        };
+        assert_eq!(lines(&mut table, perfect), 10);
+
+        let mut table = Tetris99Attack;
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::None, 2, 0)), 2);
+        assert_eq!(lines(&mut table, line_clear(2, SpinKind::None, 5, 0)), 3);
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::None, 30, 0)), 5);
+    }
+
+    #[test]
+    fn tetrio_season2() {
+        let mut table = TetrioAttack::season2();
+        assert_eq!(lines(&mut table, line_clear(4, SpinKind::None, 1, 1)), 4);
+        assert_eq!(lines(&mut table, line_clear(4, SpinKind::None, 1, 2)), 5);
+        assert_eq!(lines(&mut table, line_clear(4, SpinKind::Full, 1, 3)), 11);
+        //RENの倍率とREN数による最低保証
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::None, 2, 0)), 0);
+        assert_eq!(lines(&mut table, line_clear(1, SpinKind::None, 3, 0)), 1);
+        assert_eq!(lines(&mut table, line_clear(4, SpinKind::None, 3, 1)), 6);
+        let perfect = LineClear {
+            is_perfect: true,
+            ..line_clear(2, SpinKind::None, 1, 0)
+        };
+        assert_eq!(lines(&mut table, perfect), 6);
+    }
+
+    #[test]
+    fn tetrio_surge() {
+        let mut table = TetrioAttack::season2();
+        for b2b in 1..=5 {
+            let attack = table.attack(&line_clear(4, SpinKind::None, 1, b2b));
+            assert_eq!(attack.surge, 0);
+        }
+        //ライン消去しない固定ではB2Bは途切れない
+        assert_eq!(table.attack(&line_clear(0, SpinKind::None, 0, 5)).surge, 0);
+        let attack = table.attack(&line_clear(1, SpinKind::None, 1, 0));
+        assert_eq!((attack.lines, attack.surge), (4, 4));
+        //閾値未満で途切れたならsurgeは発生しない
+        for b2b in 1..=4 {
+            table.attack(&line_clear(4, SpinKind::None, 1, b2b));
+        }
+        let attack = table.attack(&line_clear(1, SpinKind::None, 1, 0));
+        assert_eq!((attack.lines, attack.surge), (0, 0));
+    }
+
+    #[test]
+    fn tetrio_season1_b2b_chaining() {
+        let mut table = TetrioAttack::season1();
+        for (b2b, expected) in [(2, 5), (3, 5), (4, 6), (9, 7), (25, 8)] {
+            assert_eq!(
+                lines(&mut table, line_clear(4, SpinKind::None, 1, b2b)),
+                expected
+            );
+        }
+        assert_eq!(table.attack(&line_clear(1, SpinKind::None, 1, 0)).surge, 0);
+    }
+}
@@ -0,0 +1,67 @@
+use std::process::ExitCode;
+use tetris_core_rs::Replay;
+
+//終了コード: 0 一致, 1 不一致もしくは結果が記録されていない, 2 読み込みもしくは開始に失敗
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("usage: tetris-replay <replay file>");
+        return ExitCode::from(2);
+    };
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("failed to read {}: {}", path, error);
+            return ExitCode::from(2);
+        }
+    };
+    let replay = match Replay::from_bytes(&bytes) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("failed to decode {}: {}", path, error);
+            return ExitCode::from(2);
+        }
+    };
+    let result = match replay.play() {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("failed to start {}: {}", path, error);
+            return ExitCode::from(2);
+        }
+    };
+    println!("seed: {}", replay.rng_seed);
+    println!("board: {}x{}", replay.width, replay.height);
+    println!("commands: {}", replay.commands.len());
+    if let Some(timestamp) = replay
+        .commands
+        .iter()
+        .rev()
+        .find_map(|replay_command| replay_command.timestamp)
+    {
+        println!("last timestamp: {}", timestamp);
+    }
+    println!("locked minos: {}", result.locked_minos);
+    println!("cleared lines: {}", result.cleared_lines);
+    println!("max combo: {}", result.max_combo);
+    match result.game_over {
+        Some(game_over) => println!("game over: {}", game_over),
+        None => println!("game over: none"),
+    }
+    println!("field checksum: {:016x}", result.field_checksum);
+    match replay.result {
+        Some(declared) if declared == result => {
+            println!("declared result: match");
+            ExitCode::SUCCESS
+        }
+        Some(declared) => {
+            println!("declared result: mismatch");
+            println!("  declared: {:?}", declared);
+            println!("  replayed: {:?}", result);
+            ExitCode::FAILURE
+        }
+        None => {
+            println!("declared result: missing");
+            ExitCode::FAILURE
+        }
+    }
+}
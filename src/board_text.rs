@@ -0,0 +1,235 @@
+use crate::MinoType::{self, *};
+use crate::{BoardTextError, Cell, Field};
+use core::fmt;
+
+#[cfg(not(feature = "alloc"))]
+use crate::{DEFAULT_BOARD_SIZE, create_empty_field};
+
+//'.'は空, 'X'は消去できるおじゃま, '#'は消去できないおじゃま, 'W'は壁
+//大文字は固定されたミノ, 小文字は操作中のミノ
+const EMPTY_CHAR: char = '.';
+const GARBAGE_CHAR: char = 'X';
+const SOLID_GARBAGE_CHAR: char = '#';
+const WALL_CHAR: char = 'W';
+
+fn mino_type_to_char(mino_type: MinoType) -> char {
+    match mino_type {
+        MinoI => 'I',
+        MinoO => 'O',
+        MinoT => 'T',
+        MinoS => 'S',
+        MinoZ => 'Z',
+        MinoJ => 'J',
+        MinoL => 'L',
+    }
+}
+fn char_to_mino_type(char: char) -> Option<MinoType> {
+    match char.to_ascii_uppercase() {
+        'I' => Some(MinoI),
+        'O' => Some(MinoO),
+        'T' => Some(MinoT),
+        'S' => Some(MinoS),
+        'Z' => Some(MinoZ),
+        'J' => Some(MinoJ),
+        'L' => Some(MinoL),
+        _ => None,
+    }
+}
+fn cell_to_char(cell: &Cell) -> char {
+    match cell {
+        Cell::Empty | Cell::Ghost(_) => EMPTY_CHAR, //ghostは空として出力する
+        Cell::Wall => WALL_CHAR,
+        Cell::Obstruction(true) => GARBAGE_CHAR,
+        Cell::Obstruction(false) => SOLID_GARBAGE_CHAR,
+        Cell::MinoBlock(mino_type) => mino_type_to_char(*mino_type),
+        Cell::MinoInMotion(mino_type) => mino_type_to_char(*mino_type).to_ascii_lowercase(),
+    }
+}
+fn char_to_cell(char: char) -> Result<Cell, BoardTextError> {
+    match char {
+        EMPTY_CHAR => Ok(Cell::Empty),
+        GARBAGE_CHAR => Ok(Cell::Obstruction(true)),
+        SOLID_GARBAGE_CHAR => Ok(Cell::Obstruction(false)),
+        WALL_CHAR => Ok(Cell::Wall),
+        char => match char_to_mino_type(char) {
+            Some(mino_type) if char.is_ascii_uppercase() => Ok(Cell::MinoBlock(mino_type)),
+            Some(mino_type) => Ok(Cell::MinoInMotion(mino_type)),
+            None => Err(BoardTextError::InvalidCharacter(char)),
+        },
+    }
+}
+
+//空行を除いた各行をフィールドの下に揃えて配置する
+#[cfg(feature = "alloc")]
+pub fn parse_board(text: &str, width: usize, height: usize) -> Result<Field, BoardTextError> {
+    let mut field = alloc::vec![alloc::vec![Cell::Empty; width]; height];
+    fill_field(&mut field, text, width, height)?;
+    Ok(field)
+}
+#[cfg(not(feature = "alloc"))]
+pub fn parse_board(text: &str) -> Result<Field, BoardTextError> {
+    let mut field = create_empty_field();
+    fill_field(&mut field, text, DEFAULT_BOARD_SIZE.0, DEFAULT_BOARD_SIZE.1)?;
+    Ok(field)
+}
+fn fill_field(
+    field: &mut Field,
+    text: &str,
+    width: usize,
+    height: usize,
+) -> Result<(), BoardTextError> {
+    let lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let line_count = lines.clone().count();
+    if line_count > height {
+        return Err(BoardTextError::TooManyRows(line_count));
+    }
+    for (i, line) in lines.enumerate() {
+        if line.chars().count() != width {
+            return Err(BoardTextError::RowWidthMismatch(i));
+        }
+        for (cell, char) in field[height - line_count + i].iter_mut().zip(line.chars()) {
+            *cell = char_to_cell(char)?;
+        }
+    }
+    Ok(())
+}
+
+//1行ごとに改行して出力する
+#[derive(Clone, Copy, Debug)]
+pub struct BoardText<'a, R> {
+    rows: &'a [R],
+    is_trimmed: bool,
+}
+impl<'a, R: AsRef<[Cell]>> BoardText<'a, R> {
+    pub fn new(rows: &'a [R]) -> Self {
+        Self {
+            rows,
+            is_trimmed: false,
+        }
+    }
+    //上端から続く空の行を出力しない
+    pub fn trimmed(rows: &'a [R]) -> Self {
+        Self {
+            rows,
+            is_trimmed: true,
+        }
+    }
+}
+impl<R: AsRef<[Cell]>> fmt::Display for BoardText<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let skipped = if self.is_trimmed {
+            self.rows
+                .iter()
+                .take_while(|row| {
+                    row.as_ref()
+                        .iter()
+                        .all(|cell| cell_to_char(cell) == EMPTY_CHAR)
+                })
+                .count()
+        } else {
+            0
+        };
+        for row in self.rows[skipped..].iter() {
+            for cell in row.as_ref().iter() {
+                write!(f, "{}", cell_to_char(cell))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[cfg(feature = "alloc")]
+    use crate::DEFAULT_BOARD_SIZE;
+
+    #[cfg(feature = "alloc")]
+    fn parse(text: &str) -> Result<Field, BoardTextError> {
+        parse_board(text, DEFAULT_BOARD_SIZE.0, DEFAULT_BOARD_SIZE.1)
+    }
+    #[cfg(not(feature = "alloc"))]
+    fn parse(text: &str) -> Result<Field, BoardTextError> {
+        parse_board(text)
+    }
+    fn print(board_text: BoardText<'_, impl AsRef<[Cell]>>) -> heapless::String<512> {
+        let mut text = heapless::String::new();
+        write!(text, "{}", board_text).unwrap();
+        text
+    }
+
+    const TEXT: &str = "
+        ....ss....
+        ...ss.....
+        W.XX#IIII.
+        LLLJJJOOSZ
+    ";
+
+    #[test]
+    fn aligns_rows_to_bottom() {
+        let field = parse(TEXT).unwrap();
+        let height = DEFAULT_BOARD_SIZE.1;
+        assert_eq!(field.len(), height);
+        assert!(
+            field[..height - 4]
+                .iter()
+                .flatten()
+                .all(|cell| *cell == Cell::Empty)
+        );
+        assert_eq!(field[height - 4][4], Cell::MinoInMotion(MinoS));
+        assert_eq!(field[height - 2][0], Cell::Wall);
+        assert_eq!(field[height - 2][2], Cell::Obstruction(true));
+        assert_eq!(field[height - 2][4], Cell::Obstruction(false));
+        assert_eq!(field[height - 2][5], Cell::MinoBlock(MinoI));
+        assert_eq!(field[height - 1][9], Cell::MinoBlock(MinoZ));
+    }
+
+    #[test]
+    fn round_trip() {
+        let field = parse(TEXT).unwrap();
+        let text = print(BoardText::new(&field));
+        assert_eq!(text.lines().count(), DEFAULT_BOARD_SIZE.1);
+        assert_eq!(parse(&text).unwrap(), field);
+
+        let trimmed = print(BoardText::trimmed(&field));
+        let expected = TEXT
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .fold(heapless::String::<512>::new(), |mut text, line| {
+                writeln!(text, "{}", line).unwrap();
+                text
+            });
+        assert_eq!(trimmed, expected);
+    }
+
+    #[test]
+    fn prints_ghost_as_empty() {
+        let rows = [[Cell::Ghost(MinoT), Cell::MinoInMotion(MinoT), Cell::Empty]];
+        assert_eq!(print(BoardText::new(&rows)), ".t.\n");
+        assert_eq!(print(BoardText::trimmed(&[[Cell::Ghost(MinoT)]])), "");
+    }
+
+    #[test]
+    fn rejects_invalid_text() {
+        assert_eq!(
+            parse("....?.....").unwrap_err(),
+            BoardTextError::InvalidCharacter('?')
+        );
+        assert_eq!(
+            parse("..........\n\n.........").unwrap_err(),
+            BoardTextError::RowWidthMismatch(1)
+        );
+        let mut text = heapless::String::<1024>::new();
+        for _ in 0..DEFAULT_BOARD_SIZE.1 + 1 {
+            writeln!(text, "..........").unwrap();
+        }
+        assert_eq!(
+            parse(&text).unwrap_err(),
+            BoardTextError::TooManyRows(DEFAULT_BOARD_SIZE.1 + 1)
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameOver {
+    BlockOut,   //出現位置にブロックがあり出現できない
+    LockOut,    //可視領域より上で固定された
+    TopOut,     //せり上がりでブロックがフィールドの上端を越えた
+    OutOfMinos, //固定のミノ列を使い切った
+}
+impl fmt::Display for GameOver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockOut => write!(f, "block out"),
+            Self::LockOut => write!(f, "lock out"),
+            Self::TopOut => write!(f, "top out"),
+            Self::OutOfMinos => write!(f, "out of minos"),
+        }
+    }
+}
+impl core::error::Error for GameOver {}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommandError {
+    GameOver(GameOver),         //このコマンドでゲームオーバーになった
+    AfterGameOver(GameOver),    //ゲームオーバー後にコマンドが送られた
+    HoleIndexOutOfRange(usize), //AttackedLineの穴の位置がフィールドの幅を超えている
+    Garbage(GarbageError),      //せり上がりの予告を溜められなかった
+}
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GameOver(game_over) => write!(f, "game over: {}", game_over),
+            Self::AfterGameOver(game_over) => {
+                write!(f, "command issued after game over: {}", game_over)
+            }
+            Self::HoleIndexOutOfRange(hole_index) => {
+                write!(f, "hole index {} is out of range", hole_index)
+            }
+            Self::Garbage(garbage_error) => write!(f, "garbage error: {}", garbage_error),
+        }
+    }
+}
+impl core::error::Error for CommandError {}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SequenceError {
+    Empty,   //fallback_randomizerがないのにsequenceが空
+    TooLong, //sequenceがNO_ALLOC_MINO_QUEUE_CAPACITYを超えている
+}
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty sequence without fallback randomizer"),
+            Self::TooLong => write!(f, "sequence is too long"),
+        }
+    }
+}
+impl core::error::Error for SequenceError {}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GarbageError {
+    QueueFull,    //NO_ALLOC_ATTACK_LINE_CAPACITYを超えて予告を溜めようとした
+    InvalidWidth, //幅が0のフィールドには穴を開けられない
+}
+impl fmt::Display for GarbageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::QueueFull => write!(f, "garbage queue is full"),
+            Self::InvalidWidth => write!(f, "garbage width must be positive"),
+        }
+    }
+}
+impl core::error::Error for GarbageError {}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReplayError {
+    MissingResult,           //記録時の最終状態が含まれていない
+    Sequence(SequenceError), //記録開始時のミノ列からTetrisManagerを作れない
+    Position(PositionError), //記録開始時の局面からTetrisManagerを作れない
+    FieldSizeMismatch,       //局面のフィールドのサイズがwidthとheightに一致しない
+    Mismatch {
+        recorded: crate::ReplayResult,
+        replayed: crate::ReplayResult,
+    },
+}
+#[cfg(feature = "alloc")]
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingResult => write!(f, "replay has no recorded result"),
+            Self::Sequence(sequence_error) => write!(f, "invalid replay start: {}", sequence_error),
+            Self::Position(position_error) => write!(f, "invalid replay start: {}", position_error),
+            Self::FieldSizeMismatch => write!(f, "replay position does not match the board size"),
+            Self::Mismatch { recorded, replayed } => write!(
+                f,
+                "replay result mismatch: recorded {:?}, replayed {:?}",
+                recorded, replayed
+            ),
+        }
+    }
+}
+#[cfg(feature = "alloc")]
+impl core::error::Error for ReplayError {}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReplayDecodeError {
+    InvalidMagic,                                //リプレイのデータではない
+    UnsupportedFormatVersion(u8),                //対応していないフォーマットのバージョン
+    CrateVersionMismatch(alloc::string::String), //記録時のcrateのバージョン
+    ChecksumMismatch,                            //データが破損している
+    UnexpectedEnd,
+    InvalidValue,
+}
+#[cfg(feature = "alloc")]
+impl fmt::Display for ReplayDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "not a replay"),
+            Self::UnsupportedFormatVersion(version) => {
+                write!(f, "unsupported replay format version {}", version)
+            }
+            Self::CrateVersionMismatch(version) => {
+                write!(f, "replay was recorded with crate version {}", version)
+            }
+            Self::ChecksumMismatch => write!(f, "replay checksum mismatch"),
+            Self::UnexpectedEnd => write!(f, "unexpected end of replay"),
+            Self::InvalidValue => write!(f, "invalid value in replay"),
+        }
+    }
+}
+#[cfg(feature = "alloc")]
+impl core::error::Error for ReplayDecodeError {}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FumenError {
+    UnsupportedVersion,     //v115以外
+    InvalidCharacter(char), //データに使えない文字
+    UnexpectedEnd,
+    InvalidValue,
+    InvalidFieldSize, //幅がFUMEN_WIDTHでないかFumenPage::fieldの高さがFUMEN_HEIGHTでない
+    FieldTooHigh,     //フィールドの下からFUMEN_HEIGHT行より上にブロックがある
+    PieceOutOfRange,  //ミノがフィールドの外にある
+    InvalidPieceShape, //ミノの形がSRSのどの向きとも一致しない(独自の回転法則)
+}
+impl fmt::Display for FumenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion => write!(f, "unsupported fumen version"),
+            Self::InvalidCharacter(char) => write!(f, "invalid character {:?} in fumen", char),
+            Self::UnexpectedEnd => write!(f, "unexpected end of fumen"),
+            Self::InvalidValue => write!(f, "invalid value in fumen"),
+            Self::InvalidFieldSize => write!(f, "field size is not supported by fumen"),
+            Self::FieldTooHigh => write!(f, "field is too high for fumen"),
+            Self::PieceOutOfRange => write!(f, "piece is out of the fumen field"),
+            Self::InvalidPieceShape => write!(f, "piece shape does not match any SRS direction"),
+        }
+    }
+}
+impl core::error::Error for FumenError {}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BoardTextError {
+    InvalidCharacter(char),
+    RowWidthMismatch(usize), //幅がフィールドと異なる行の番号(空行を除いて上から数える)
+    TooManyRows(usize),      //行数がフィールドの高さを超えている
+}
+impl fmt::Display for BoardTextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCharacter(char) => write!(f, "invalid character {:?} in board", char),
+            Self::RowWidthMismatch(row) => write!(f, "row {} does not match the field width", row),
+            Self::TooManyRows(rows) => write!(f, "{} rows exceed the field height", rows),
+        }
+    }
+}
+impl core::error::Error for BoardTextError {}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PositionError {
+    InvalidFieldSize,                   //フィールドが空か行ごとに幅が異なる
+    InvalidCell { x: usize, y: usize }, //フィールドに操作中のミノかghostが含まれている
+    PieceCollision,                     //指定した位置にミノを置けない
+    HeldWithoutHold,                    //holdが空なのにhas_heldがtrue
+    Sequence(SequenceError),            //currentとqueueからミノ列を作れない
+    FullRow { y: usize },               //消去されるはずの揃った行がある
+    SpawnOutOfField,                    //フィールドが狭くて出現位置にミノが収まらない
+}
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFieldSize => write!(f, "invalid field size"),
+            Self::InvalidCell { x, y } => write!(f, "invalid cell at ({}, {})", x, y),
+            Self::PieceCollision => write!(f, "current piece collides with the field"),
+            Self::HeldWithoutHold => write!(f, "has_held is set without a hold piece"),
+            Self::Sequence(sequence_error) => write!(f, "invalid queue: {}", sequence_error),
+            Self::FullRow { y } => write!(f, "row {} is already full", y),
+            Self::SpawnOutOfField => write!(f, "field is too small to spawn pieces"),
+        }
+    }
+}
+impl core::error::Error for PositionError {}
@@ -0,0 +1,58 @@
+use crate::{GameOver, MinoDirection, MinoType, SpinKind};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "alloc"))]
+use heapless::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub type Cells = heapless::Vec<(usize, usize), 16>; //(x, y)
+#[cfg(feature = "alloc")]
+pub type ClearedRows = Vec<usize>;
+#[cfg(not(feature = "alloc"))]
+pub type ClearedRows = Vec<usize, { crate::DEFAULT_BOARD_SIZE.1 }>; //フィールドの高さ
+
+#[cfg(feature = "alloc")]
+pub(crate) type Events = Vec<Event>;
+#[cfg(not(feature = "alloc"))]
+pub(crate) type Events = Vec<Event, { crate::NO_ALLOC_EVENT_CAPACITY }>;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    PieceMoved {
+        x: i64, //移動後の4x4の枠の位置
+        y: i64,
+    },
+    Rotated {
+        direction: MinoDirection,
+        kick_index: usize,
+    },
+    Spun {
+        mino_type: MinoType,
+        spin: SpinKind,
+    },
+    Locked {
+        mino_type: MinoType,
+        cells: Cells,
+    },
+    LinesCleared {
+        rows: ClearedRows, //消去される前の行の番号
+    },
+    GarbageInserted {
+        lines: usize,
+    },
+    Spawned {
+        mino_type: MinoType,
+        x: i64,
+        y: i64,
+        direction: MinoDirection,
+    },
+    Held {
+        mino_type: MinoType, //holdされたミノ
+    },
+    GameOver(GameOver),
+}
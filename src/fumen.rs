@@ -0,0 +1,860 @@
+use crate::MinoDirection::{self, *};
+use crate::MinoType::{self, *};
+use crate::{Cell, FumenError, Randomizer, TetrisManager};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const FUMEN_WIDTH: usize = 10;
+pub const FUMEN_HEIGHT: usize = 23;
+const FUMEN_ROWS: usize = FUMEN_HEIGHT + 1; //一番下の行はせり上がるライン
+const FUMEN_BLOCKS: usize = FUMEN_ROWS * FUMEN_WIDTH;
+const VERSION_PREFIXES: [&str; 3] = ["v115@", "m115@", "d115@"];
+const ENCODE_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8; 95] = b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_CHAR_VALUES: u64 = COMMENT_TABLE.len() as u64 + 1;
+const MAX_COMMENT_LENGTH: usize = 4095;
+const GARBAGE_CODE: u8 = 8;
+
+//上の行から順に並べたブロック(0は空,1-7はミノ,8はおじゃま)
+type Blocks = [[u8; FUMEN_WIDTH]; FUMEN_ROWS];
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FumenPiece {
+    pub mino_type: MinoType,
+    pub direction: MinoDirection,
+    pub x: i64, //SRSの回転中心の位置
+    pub y: i64, //下から数える
+}
+impl FumenPiece {
+    //FumenPage::fieldでの位置(x, y)
+    pub fn cells(&self) -> [(i64, i64); 4] {
+        piece_offsets(self.mino_type, self.direction)
+            .map(|(dx, dy)| (self.x + dx, FUMEN_HEIGHT as i64 - 1 - (self.y + dy)))
+    }
+    //SRS以外の回転法則では同じ向きでも形が異なるので、cellsと同じ形になるSRSの向きを探す(directionを優先する)
+    fn from_cells(
+        mino_type: MinoType,
+        direction: MinoDirection,
+        cells: &[(i64, i64)],
+    ) -> Result<Self, FumenError> {
+        let mut cells = cells.to_vec();
+        cells.sort();
+        let min_x = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let min_y = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        [direction, North, East, South, West]
+            .into_iter()
+            .map(|direction| {
+                let offsets = piece_offsets(mino_type, direction);
+                let x = min_x - offsets.iter().map(|&(dx, _)| dx).min().unwrap_or(0);
+                let y = min_y - offsets.iter().map(|&(_, dy)| dy).min().unwrap_or(0);
+                (direction, x, y, offsets)
+            })
+            .find(|(_, x, y, offsets)| {
+                let mut piece_cells = offsets.map(|(dx, dy)| (x + dx, y + dy));
+                piece_cells.sort();
+                piece_cells == *cells
+            })
+            .map(|(direction, x, y, _)| Self {
+                mino_type,
+                direction,
+                x,
+                y,
+            })
+            .ok_or(FumenError::InvalidPieceShape)
+    }
+    fn is_in_field(&self) -> bool {
+        self.cells().iter().all(|&(x, y)| {
+            (0..FUMEN_WIDTH as i64).contains(&x) && (0..FUMEN_HEIGHT as i64).contains(&y)
+        })
+    }
+}
+//回転中心からの位置(y上向きが正)
+fn piece_offsets(mino_type: MinoType, direction: MinoDirection) -> [(i64, i64); 4] {
+    let offsets = match mino_type {
+        MinoI => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        MinoT => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        MinoO => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        MinoL => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        MinoJ => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        MinoS => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        MinoZ => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    };
+    offsets.map(|(x, y)| match direction {
+        North => (x, y),
+        East => (y, -x),
+        South => (-x, -y),
+        West => (-y, x),
+    })
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FumenFlags {
+    pub lock: bool, //次のページでミノを固定してライン消去する
+    pub rise: bool, //固定後にgarbage_rowがせり上がる
+    pub mirror: bool,
+    pub colorize: bool,
+}
+impl Default for FumenFlags {
+    fn default() -> Self {
+        Self {
+            lock: true,
+            rise: false,
+            mirror: false,
+            colorize: true,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FumenPage {
+    pub field: Vec<Vec<Cell>>, //FUMEN_WIDTH x FUMEN_HEIGHT
+    pub garbage_row: Vec<Cell>,
+    pub piece: Option<FumenPiece>,
+    pub comment: String,
+    pub flags: FumenFlags,
+}
+impl Default for FumenPage {
+    fn default() -> Self {
+        Self {
+            field: alloc::vec![alloc::vec![Cell::Empty; FUMEN_WIDTH]; FUMEN_HEIGHT],
+            garbage_row: alloc::vec![Cell::Empty; FUMEN_WIDTH],
+            piece: None,
+            comment: String::new(),
+            flags: FumenFlags::default(),
+        }
+    }
+}
+impl FumenPage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn to_blocks(&self) -> Result<Blocks, FumenError> {
+        if self.field.len() != FUMEN_HEIGHT
+            || self
+                .field
+                .iter()
+                .chain(core::iter::once(&self.garbage_row))
+                .any(|row| row.len() != FUMEN_WIDTH)
+        {
+            return Err(FumenError::InvalidFieldSize);
+        }
+        let mut blocks = [[0; FUMEN_WIDTH]; FUMEN_ROWS];
+        for (blocks_row, row) in blocks
+            .iter_mut()
+            .zip(self.field.iter().chain(core::iter::once(&self.garbage_row)))
+        {
+            for (block, cell) in blocks_row.iter_mut().zip(row.iter()) {
+                *block = cell_to_code(cell);
+            }
+        }
+        Ok(blocks)
+    }
+    fn from_blocks(blocks: &Blocks) -> Self {
+        let to_row = |row: &[u8; FUMEN_WIDTH]| row.iter().map(|&code| code_to_cell(code)).collect();
+        Self {
+            field: blocks[..FUMEN_HEIGHT].iter().map(to_row).collect(),
+            garbage_row: to_row(&blocks[FUMEN_HEIGHT]),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Fumen {
+    pub pages: Vec<FumenPage>,
+}
+impl Fumen {
+    pub fn new(pages: Vec<FumenPage>) -> Self {
+        Self { pages }
+    }
+    pub fn encode(&self) -> Result<String, FumenError> {
+        let mut values = Vec::new();
+        let mut previous_blocks = [[0; FUMEN_WIDTH]; FUMEN_ROWS];
+        let mut previous_comment = "";
+        let mut repeat_index: Option<usize> = None; //フィールドが変化しなかったページ数を記録した位置
+        for page in self.pages.iter() {
+            let mut blocks = page.to_blocks()?;
+            let (is_changed, field_values) = encode_field(&previous_blocks, &blocks);
+            match repeat_index {
+                Some(index) if !is_changed && values[index] < ENCODE_TABLE.len() as u8 - 1 => {
+                    values[index] += 1;
+                }
+                _ => {
+                    values.extend(field_values);
+                    repeat_index = (!is_changed).then(|| {
+                        values.push(0);
+                        values.len() - 1
+                    });
+                }
+            }
+            if let Some(piece) = &page.piece
+                && !piece.is_in_field()
+            {
+                return Err(FumenError::PieceOutOfRange);
+            }
+            let has_comment = page.comment != previous_comment;
+            push_value(&mut values, encode_action(page, has_comment), 3);
+            if has_comment {
+                let escaped = escape(&page.comment, MAX_COMMENT_LENGTH);
+                let escaped = escaped.as_bytes();
+                push_value(&mut values, escaped.len() as u64, 2);
+                for chunk in escaped.chunks(4) {
+                    let value = chunk.iter().rev().fold(0, |value, &char| {
+                        let index = COMMENT_TABLE.iter().position(|&c| c == char).unwrap_or(0);
+                        value * COMMENT_CHAR_VALUES + index as u64
+                    });
+                    push_value(&mut values, value, 5);
+                }
+                previous_comment = &page.comment;
+            }
+            apply_action(&mut blocks, page);
+            previous_blocks = blocks;
+        }
+        let data: Vec<u8> = values
+            .iter()
+            .map(|&value| ENCODE_TABLE[value as usize])
+            .collect();
+        //長いときは最初の42文字の後に47文字ごとに?を挟む
+        let mut fumen = String::from(VERSION_PREFIXES[0]);
+        for (i, chunk) in core::iter::once(&data[..data.len().min(42)])
+            .chain(data.get(42..).unwrap_or_default().chunks(47))
+            .enumerate()
+        {
+            if i > 0 {
+                fumen.push('?');
+            }
+            fumen.extend(chunk.iter().map(|&c| c as char));
+        }
+        Ok(fumen)
+    }
+    //URLなどの前後の文字が含まれていてもよい
+    pub fn decode(fumen: &str) -> Result<Self, FumenError> {
+        let data = VERSION_PREFIXES
+            .iter()
+            .find_map(|prefix| {
+                fumen
+                    .find(prefix)
+                    .map(|index| &fumen[index + prefix.len()..])
+            })
+            .ok_or(FumenError::UnsupportedVersion)?;
+        let mut values = Vec::new();
+        for char in data.chars() {
+            match char {
+                '?' => {}
+                char if char.is_whitespace() => {}
+                char => values.push(
+                    ENCODE_TABLE
+                        .iter()
+                        .position(|&c| c as char == char)
+                        .ok_or(FumenError::InvalidCharacter(char))? as u8,
+                ),
+            }
+        }
+        let mut reader = ValueReader {
+            values: &values,
+            position: 0,
+        };
+        let mut pages = Vec::new();
+        let mut previous_blocks = [[0; FUMEN_WIDTH]; FUMEN_ROWS];
+        let mut comment = String::new();
+        let mut repeat_count = 0;
+        while !reader.is_empty() {
+            let blocks = if repeat_count > 0 {
+                repeat_count -= 1;
+                previous_blocks
+            } else {
+                let (is_changed, blocks) = decode_field(&mut reader, &previous_blocks)?;
+                if !is_changed {
+                    repeat_count = reader.poll(1)?;
+                }
+                blocks
+            };
+            let (piece, flags, has_comment) = decode_action(reader.poll(3)?)?;
+            if has_comment {
+                let length = reader.poll(2)? as usize;
+                let mut escaped = String::new();
+                for _ in 0..length.div_ceil(4) {
+                    let mut value = reader.poll(5)?;
+                    for _ in 0..4 {
+                        let char = COMMENT_TABLE
+                            .get((value % COMMENT_CHAR_VALUES) as usize)
+                            .ok_or(FumenError::InvalidValue)?;
+                        escaped.push(*char as char);
+                        value /= COMMENT_CHAR_VALUES;
+                    }
+                }
+                escaped.truncate(length);
+                comment = unescape(&escaped)?;
+            }
+            let page = FumenPage {
+                piece,
+                comment: comment.clone(),
+                flags,
+                ..FumenPage::from_blocks(&blocks)
+            };
+            let mut blocks = blocks;
+            apply_action(&mut blocks, &page);
+            previous_blocks = blocks;
+            pages.push(page);
+        }
+        Ok(Self { pages })
+    }
+}
+
+impl<R: Randomizer> TetrisManager<R> {
+    //フィールドの下からFUMEN_HEIGHT行と操作中のミノをページにする
+    pub fn to_fumen_page(&self) -> Result<FumenPage, FumenError> {
+        if self.width != FUMEN_WIDTH {
+            return Err(FumenError::InvalidFieldSize);
+        }
+        let hidden_rows = self.height.saturating_sub(FUMEN_HEIGHT);
+        if self.field[..hidden_rows]
+            .iter()
+            .any(|row| row.iter().any(|cell| *cell != Cell::Empty))
+        {
+            return Err(FumenError::FieldTooHigh);
+        }
+        let mut page = FumenPage::new();
+        let top = FUMEN_HEIGHT - (self.height - hidden_rows);
+        for (page_row, row) in page.field[top..]
+            .iter_mut()
+            .zip(self.field[hidden_rows..].iter())
+        {
+            page_row.clone_from(row);
+        }
+        if self.game_over.is_none() {
+            let cells: Vec<(i64, i64)> = self
+                .current_mino
+                .cells()
+                .iter()
+                .map(|&(x, y)| (x as i64, self.height as i64 - 1 - y as i64))
+                .collect();
+            let piece = FumenPiece::from_cells(
+                self.current_mino.mino_type,
+                self.current_mino.mino_direction,
+                &cells,
+            )?;
+            if !piece.is_in_field() {
+                return Err(FumenError::PieceOutOfRange);
+            }
+            page.piece = Some(piece);
+        }
+        Ok(page)
+    }
+}
+
+struct ValueReader<'a> {
+    values: &'a [u8],
+    position: usize,
+}
+impl ValueReader<'_> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.values.len()
+    }
+    //下の桁から順に並んでいる
+    fn poll(&mut self, digits: usize) -> Result<u64, FumenError> {
+        let values = self
+            .values
+            .get(self.position..self.position + digits)
+            .ok_or(FumenError::UnexpectedEnd)?;
+        self.position += digits;
+        Ok(values.iter().rev().fold(0, |value, &digit| {
+            value * ENCODE_TABLE.len() as u64 + digit as u64
+        }))
+    }
+}
+fn push_value(values: &mut Vec<u8>, mut value: u64, digits: usize) {
+    for _ in 0..digits {
+        values.push((value % ENCODE_TABLE.len() as u64) as u8);
+        value /= ENCODE_TABLE.len() as u64;
+    }
+}
+
+//直前のページとの差分を(差分+8, 連続するブロック数-1)の組で記録する
+fn encode_field(previous_blocks: &Blocks, blocks: &Blocks) -> (bool, Vec<u8>) {
+    let mut values = Vec::new();
+    let diffs = previous_blocks
+        .iter()
+        .flatten()
+        .zip(blocks.iter().flatten())
+        .map(|(&previous, &current)| current as u64 + GARBAGE_CODE as u64 - previous as u64);
+    let mut run: Option<(u64, u64)> = None;
+    for diff in diffs {
+        run = match run {
+            Some((run_diff, count)) if run_diff == diff => Some((run_diff, count + 1)),
+            Some((run_diff, count)) => {
+                push_value(&mut values, run_diff * FUMEN_BLOCKS as u64 + count, 2);
+                Some((diff, 0))
+            }
+            None => Some((diff, 0)),
+        };
+    }
+    let (run_diff, count) = run.unwrap_or_default();
+    push_value(&mut values, run_diff * FUMEN_BLOCKS as u64 + count, 2);
+    let is_changed = !(run_diff == GARBAGE_CODE as u64 && count == FUMEN_BLOCKS as u64 - 1);
+    (is_changed, values)
+}
+fn decode_field(
+    reader: &mut ValueReader,
+    previous_blocks: &Blocks,
+) -> Result<(bool, Blocks), FumenError> {
+    let mut blocks = *previous_blocks;
+    let mut index = 0;
+    let mut is_changed = true;
+    while index < FUMEN_BLOCKS {
+        let value = reader.poll(2)?;
+        let diff = value / FUMEN_BLOCKS as u64;
+        let count = (value % FUMEN_BLOCKS as u64) as usize + 1;
+        if index + count > FUMEN_BLOCKS {
+            return Err(FumenError::InvalidValue);
+        }
+        if diff == GARBAGE_CODE as u64 && count == FUMEN_BLOCKS {
+            is_changed = false;
+        }
+        for i in index..index + count {
+            let block = &mut blocks[i / FUMEN_WIDTH][i % FUMEN_WIDTH];
+            *block = (*block as u64 + diff)
+                .checked_sub(GARBAGE_CODE as u64)
+                .filter(|&code| code <= GARBAGE_CODE as u64)
+                .ok_or(FumenError::InvalidValue)? as u8;
+        }
+        index += count;
+    }
+    Ok((is_changed, blocks))
+}
+
+fn encode_action(page: &FumenPage, has_comment: bool) -> u64 {
+    let (piece_code, rotation, position) = match &page.piece {
+        Some(piece) => {
+            //回転中心ではなく旧来の基準点で記録する
+            let (dx, dy) = match (piece.mino_type, piece.direction) {
+                (MinoO, West) => (-1, 1),
+                (MinoO, South) => (-1, 0),
+                (MinoO, North) => (0, 1),
+                (MinoI, South) => (-1, 0),
+                (MinoI, West) => (0, 1),
+                (MinoS, North) => (0, 1),
+                (MinoS, East) => (1, 0),
+                (MinoZ, North) => (0, 1),
+                (MinoZ, West) => (-1, 0),
+                _ => (0, 0),
+            };
+            let (x, y) = (piece.x + dx, piece.y + dy);
+            (
+                mino_type_to_code(piece.mino_type) as u64,
+                direction_to_code(piece.direction),
+                ((FUMEN_HEIGHT as i64 - y - 1) * FUMEN_WIDTH as i64 + x) as u64,
+            )
+        }
+        None => (0, direction_to_code(South), 0),
+    };
+    let flags = [
+        !page.flags.lock,
+        has_comment,
+        page.flags.colorize,
+        page.flags.mirror,
+        page.flags.rise,
+    ];
+    let value = flags.iter().fold(0, |value, &flag| value * 2 + flag as u64);
+    ((value * FUMEN_BLOCKS as u64 + position) * 4 + rotation) * 8 + piece_code
+}
+fn decode_action(value: u64) -> Result<(Option<FumenPiece>, FumenFlags, bool), FumenError> {
+    let piece_code = (value % 8) as u8;
+    let direction = code_to_direction(value / 8 % 4);
+    let position = (value / 32 % FUMEN_BLOCKS as u64) as i64;
+    let flags = value / 32 / FUMEN_BLOCKS as u64;
+    let piece = match code_to_mino_type(piece_code) {
+        Some(mino_type) => {
+            let (x, y) = (
+                position % FUMEN_WIDTH as i64,
+                FUMEN_HEIGHT as i64 - position / FUMEN_WIDTH as i64 - 1,
+            );
+            let (dx, dy) = match (mino_type, direction) {
+                (MinoO, West) => (1, -1),
+                (MinoO, South) => (1, 0),
+                (MinoO, North) => (0, -1),
+                (MinoI, South) => (1, 0),
+                (MinoI, West) => (0, -1),
+                (MinoS, North) => (0, -1),
+                (MinoS, East) => (-1, 0),
+                (MinoZ, North) => (0, -1),
+                (MinoZ, West) => (1, 0),
+                _ => (0, 0),
+            };
+            let piece = FumenPiece {
+                mino_type,
+                direction,
+                x: x + dx,
+                y: y + dy,
+            };
+            if !piece.is_in_field() {
+                return Err(FumenError::PieceOutOfRange);
+            }
+            Some(piece)
+        }
+        None => None,
+    };
+    let flags_at = |shift: u64| (flags >> shift) & 1 == 1;
+    Ok((
+        piece,
+        FumenFlags {
+            lock: !flags_at(4),
+            colorize: flags_at(2),
+            mirror: flags_at(1),
+            rise: flags_at(0),
+        },
+        flags_at(3),
+    ))
+}
+//ミノを固定してライン消去し、flagsに応じてせり上げと左右反転をする
+fn apply_action(blocks: &mut Blocks, page: &FumenPage) {
+    if !page.flags.lock {
+        return;
+    }
+    if let Some(piece) = &page.piece {
+        for (x, y) in piece.cells() {
+            blocks[y as usize][x as usize] = mino_type_to_code(piece.mino_type);
+        }
+    }
+    let mut rows: Vec<[u8; FUMEN_WIDTH]> = blocks[..FUMEN_HEIGHT]
+        .iter()
+        .filter(|row| row.contains(&0))
+        .copied()
+        .collect();
+    while rows.len() < FUMEN_HEIGHT {
+        rows.insert(0, [0; FUMEN_WIDTH]);
+    }
+    if page.flags.rise {
+        rows.remove(0);
+        rows.push(blocks[FUMEN_HEIGHT]);
+        blocks[FUMEN_HEIGHT] = [0; FUMEN_WIDTH];
+    }
+    if page.flags.mirror {
+        for row in rows.iter_mut() {
+            row.reverse();
+        }
+    }
+    blocks[..FUMEN_HEIGHT].copy_from_slice(&rows);
+}
+
+fn mino_type_to_code(mino_type: MinoType) -> u8 {
+    match mino_type {
+        MinoI => 1,
+        MinoL => 2,
+        MinoO => 3,
+        MinoZ => 4,
+        MinoT => 5,
+        MinoJ => 6,
+        MinoS => 7,
+    }
+}
+fn code_to_mino_type(code: u8) -> Option<MinoType> {
+    match code {
+        1 => Some(MinoI),
+        2 => Some(MinoL),
+        3 => Some(MinoO),
+        4 => Some(MinoZ),
+        5 => Some(MinoT),
+        6 => Some(MinoJ),
+        7 => Some(MinoS),
+        _ => None,
+    }
+}
+fn cell_to_code(cell: &Cell) -> u8 {
+    match cell {
+        Cell::Empty | Cell::Ghost(_) => 0,
+        Cell::MinoBlock(mino_type) | Cell::MinoInMotion(mino_type) => mino_type_to_code(*mino_type),
+        Cell::Wall | Cell::Obstruction(_) => GARBAGE_CODE,
+    }
+}
+fn code_to_cell(code: u8) -> Cell {
+    match code_to_mino_type(code) {
+        Some(mino_type) => Cell::MinoBlock(mino_type),
+        None if code == GARBAGE_CODE => Cell::Obstruction(true),
+        None => Cell::Empty,
+    }
+}
+fn direction_to_code(direction: MinoDirection) -> u64 {
+    match direction {
+        South => 0,
+        East => 1,
+        North => 2,
+        West => 3,
+    }
+}
+fn code_to_direction(code: u64) -> MinoDirection {
+    match code {
+        0 => South,
+        1 => East,
+        2 => North,
+        _ => West,
+    }
+}
+
+//JavaScriptのescape/unescapeと同じ変換
+//max_lengthを超える場合は%XXやサロゲートペアの途中で切らずに文字の区切りで打ち切る
+fn escape(text: &str, max_length: usize) -> String {
+    let mut escaped = String::new();
+    let mut escaped_char = String::new();
+    for char in text.chars() {
+        escaped_char.clear();
+        for unit in char.encode_utf16(&mut [0; 2]).iter().copied() {
+            match char::from_u32(unit as u32) {
+                Some(char) if char.is_ascii_alphanumeric() || "@*_+-./".contains(char) => {
+                    escaped_char.push(char)
+                }
+                _ if unit < 0x100 => {
+                    let _ = write!(escaped_char, "%{:02X}", unit);
+                }
+                _ => {
+                    let _ = write!(escaped_char, "%u{:04X}", unit);
+                }
+            }
+        }
+        if escaped.len() + escaped_char.len() > max_length {
+            break;
+        }
+        escaped.push_str(&escaped_char);
+    }
+    escaped
+}
+fn unescape(escaped: &str) -> Result<String, FumenError> {
+    let mut units = Vec::new();
+    let mut rest = escaped;
+    while let Some(char) = rest.chars().next() {
+        let parse = |digits: usize, skip: usize| {
+            rest.get(skip..skip + digits)
+                .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                .map(|unit| (unit, skip + digits))
+        };
+        let (unit, len) = match char {
+            '%' => parse(4, 2)
+                .filter(|_| rest[1..].starts_with('u'))
+                .or_else(|| parse(2, 1))
+                .unwrap_or(('%' as u16, 1)),
+            char => (char as u16, char.len_utf8()),
+        };
+        units.push(unit);
+        rest = &rest[len..];
+    }
+    String::from_utf16(&units).map_err(|_| FumenError::InvalidValue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MovementCommand, RotationSystem, TetrisConfig};
+
+    fn assert_round_trip(fumen: &str) -> Fumen {
+        let decoded = Fumen::decode(fumen).unwrap();
+        assert_eq!(decoded.encode().unwrap(), fumen);
+        decoded
+    }
+    fn field_cells(page: &FumenPage) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        for (y, row) in page.field.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if *cell != Cell::Empty {
+                    cells.push((x as i64, y as i64));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn empty_field() {
+        let fumen = assert_round_trip("v115@vhAAgH");
+        assert_eq!(fumen.pages, [FumenPage::new()]);
+    }
+
+    #[test]
+    fn garbage_field() {
+        let fumen = assert_round_trip("v115@9gF8DeF8DeF8DeF8NeAgH");
+        let page = &fumen.pages[0];
+        for row in page.field[19..].iter() {
+            assert!(row[..6].iter().all(|cell| *cell == Cell::Obstruction(true)));
+            assert!(row[6..].iter().all(|cell| *cell == Cell::Empty));
+        }
+        assert_eq!(field_cells(page).len(), 24);
+        assert_eq!(page.piece, None);
+    }
+
+    //tetris-fumenは旧来の基準点から回転中心へO(Spawn)はy-1, I(Reverse)はx+1, S(Right)はx-1する
+    #[test]
+    fn legacy_anchors() {
+        let fumen = assert_round_trip("v115@vhATJJ");
+        let piece = fumen.pages[0].piece.unwrap();
+        assert_eq!(
+            piece,
+            FumenPiece {
+                mino_type: MinoO,
+                direction: North,
+                x: 0,
+                y: 0,
+            }
+        );
+        let mut cells = piece.cells();
+        cells.sort();
+        assert_eq!(cells, [(0, 21), (0, 22), (1, 21), (1, 22)]);
+
+        let fumen = assert_round_trip("v115@vhABQJ");
+        let piece = fumen.pages[0].piece.unwrap();
+        assert_eq!((piece.mino_type, piece.direction), (MinoI, South));
+        assert_eq!((piece.x, piece.y), (5, 0));
+        let mut cells = piece.cells();
+        cells.sort();
+        assert_eq!(cells, [(3, 22), (4, 22), (5, 22), (6, 22)]);
+
+        let fumen = assert_round_trip("v115@vhAPKJ");
+        let piece = fumen.pages[0].piece.unwrap();
+        assert_eq!((piece.mino_type, piece.direction), (MinoS, East));
+        assert_eq!((piece.x, piece.y), (1, 1));
+        let mut cells = piece.cells();
+        cells.sort();
+        assert_eq!(cells, [(1, 20), (1, 21), (2, 21), (2, 22)]);
+    }
+
+    #[test]
+    fn locks_piece_on_next_page() {
+        //2ページ目はフィールドが変化しないページ
+        let decoded = assert_round_trip("v115@vhBxOJAgH");
+        assert_eq!(
+            decoded.pages[0].piece,
+            Some(FumenPiece {
+                mino_type: MinoI,
+                direction: North,
+                x: 1,
+                y: 0,
+            })
+        );
+        assert_eq!(
+            field_cells(&decoded.pages[1]),
+            [(0, 22), (1, 22), (2, 22), (3, 22)]
+        );
+        assert_eq!(decoded.pages[1].field[22][0], Cell::MinoBlock(MinoI));
+    }
+
+    #[test]
+    fn piece_at_top_row() {
+        let mut page = FumenPage::new();
+        for direction in [North, East, South, West] {
+            let offsets = piece_offsets(MinoO, direction);
+            page.piece = Some(FumenPiece {
+                mino_type: MinoO,
+                direction,
+                x: -offsets.iter().map(|&(dx, _)| dx).min().unwrap(),
+                y: FUMEN_HEIGHT as i64 - 1 - offsets.iter().map(|&(_, dy)| dy).max().unwrap(),
+            });
+            let fumen = Fumen::new(alloc::vec![page.clone()]).encode().unwrap();
+            assert_eq!(Fumen::decode(&fumen).unwrap().pages, [page.clone()]);
+        }
+    }
+
+    #[test]
+    fn repeated_pages_and_comments() {
+        let mut page = FumenPage::new();
+        page.flags.lock = false;
+        page.comment = String::from("Tetris 100% ネクスト");
+        let pages = alloc::vec![page; 70];
+        let fumen = Fumen::new(pages.clone()).encode().unwrap();
+        assert_eq!(Fumen::decode(&fumen).unwrap().pages, pages);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_eq!(
+            Fumen::decode("v110@vhAAgH"),
+            Err(FumenError::UnsupportedVersion)
+        );
+        assert_eq!(
+            Fumen::decode("v115@vh!AgH"),
+            Err(FumenError::InvalidCharacter('!'))
+        );
+        let mut page = FumenPage::new();
+        page.piece = Some(FumenPiece {
+            mino_type: MinoI,
+            direction: North,
+            x: 0,
+            y: 0,
+        });
+        assert_eq!(
+            Fumen::new(alloc::vec![page]).encode(),
+            Err(FumenError::PieceOutOfRange)
+        );
+    }
+
+    #[test]
+    fn long_comment_is_truncated_between_characters() {
+        let encode_comment = |comment: String| {
+            let mut page = FumenPage::new();
+            page.comment = comment;
+            let fumen = Fumen::new(alloc::vec![page]).encode().unwrap();
+            Fumen::decode(&fumen).unwrap().pages[0].comment.clone()
+        };
+        //%uXXXXは6文字なので682文字(4092文字)まで
+        let comment = encode_comment("あ".repeat(700));
+        assert_eq!(comment, "あ".repeat(682));
+        //%XXは3文字なので先頭の1文字と1364文字(4093文字)まで
+        let comment = encode_comment(String::from("a") + &"!".repeat(2000));
+        assert_eq!(comment, String::from("a") + &"!".repeat(1364));
+        //サロゲートペアは2つ合わせて12文字で、途中では切らない
+        let comment = encode_comment(String::from("abc") + &"😀".repeat(400));
+        assert_eq!(comment, String::from("abc") + &"😀".repeat(341));
+    }
+
+    #[test]
+    fn exports_pieces_of_other_rotation_systems() {
+        for rotation_system in [
+            RotationSystem::Ars,
+            RotationSystem::Nrs,
+            RotationSystem::Srs,
+        ] {
+            for mino_type in crate::MINO_ARRAY {
+                for rotations in 0..4 {
+                    let tetris_config = TetrisConfig {
+                        rotation_system: rotation_system.clone(),
+                        ..TetrisConfig::default()
+                    };
+                    let mut tetris_manager: TetrisManager = TetrisManager::with_sequence(
+                        tetris_config,
+                        &[mino_type],
+                        None,
+                        None,
+                        &0,
+                        10,
+                        42,
+                    )
+                    .unwrap();
+                    for _ in 0..rotations {
+                        let _ = tetris_manager.command(MovementCommand::RotateClockWise);
+                    }
+                    let hidden_rows = 42 - FUMEN_HEIGHT;
+                    let mut expected: Vec<(i64, i64)> = tetris_manager
+                        .current_mino
+                        .cells()
+                        .iter()
+                        .map(|&(x, y)| (x as i64, (y - hidden_rows) as i64))
+                        .collect();
+                    expected.sort();
+                    let page = tetris_manager.to_fumen_page().unwrap();
+                    let mut cells = page.piece.unwrap().cells();
+                    cells.sort();
+                    assert_eq!(
+                        cells, *expected,
+                        "{rotation_system:?} {mino_type:?} {rotations}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::{AttackedLine, AttackedLines, GarbageError};
+use core::hash::Hash;
+use rand::Rng;
+use rand_chacha::ChaCha20Rng;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "alloc"))]
+use heapless::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GarbageDelay {
+    Pieces(usize), //指定した数のミノが固定されるまで待つ
+    Frames(usize), //MovementCommand::ElapseGarbageFramesで指定したフレーム数が経過するまで待つ
+}
+impl Default for GarbageDelay {
+    fn default() -> Self {
+        Self::Pieces(0)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingGarbage {
+    pub attacked_line: AttackedLine,
+    pub delay: usize, //0になるとミノの固定時にフィールドに挿入される
+}
+
+#[cfg(feature = "alloc")]
+type PendingGarbages = Vec<PendingGarbage>;
+#[cfg(not(feature = "alloc"))]
+type PendingGarbages = Vec<PendingGarbage, { crate::NO_ALLOC_ATTACK_LINE_CAPACITY }>;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct GarbageQueue {
+    pending_garbages: PendingGarbages,
+}
+impl GarbageQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, attacked_line: AttackedLine, delay: usize) -> Result<(), GarbageError> {
+        let pending_garbage = PendingGarbage {
+            attacked_line,
+            delay,
+        };
+        #[cfg(feature = "alloc")]
+        self.pending_garbages.push(pending_garbage);
+        #[cfg(not(feature = "alloc"))]
+        self.pending_garbages
+            .push(pending_garbage)
+            .map_err(|_| GarbageError::QueueFull)?;
+        Ok(())
+    }
+    //古いものから相殺し、相殺しきれなかったライン数を返す
+    pub fn cancel(&mut self, lines: usize) -> usize {
+        let canceled = lines.min(self.pending_garbages.len());
+        #[cfg(feature = "alloc")]
+        self.pending_garbages.drain(..canceled);
+        #[cfg(not(feature = "alloc"))]
+        {
+            self.pending_garbages.rotate_left(canceled);
+            self.pending_garbages
+                .truncate(self.pending_garbages.len() - canceled);
+        }
+        lines - canceled
+    }
+    pub fn elapse(&mut self, amount: usize) {
+        for pending_garbage in self.pending_garbages.iter_mut() {
+            pending_garbage.delay = pending_garbage.delay.saturating_sub(amount);
+        }
+    }
+    //delayが0になったものを古い順にcap個まで取り出す
+    pub fn pop_ready(&mut self, cap: Option<usize>) -> AttackedLines {
+        let mut ready = AttackedLines::new();
+        let mut remaining = PendingGarbages::new();
+        for pending_garbage in self.pending_garbages.iter() {
+            if pending_garbage.delay == 0 && cap.is_none_or(|cap| ready.len() < cap) {
+                #[cfg(feature = "alloc")]
+                ready.push(pending_garbage.attacked_line.clone());
+                #[cfg(not(feature = "alloc"))]
+                let _ = ready.push(pending_garbage.attacked_line.clone());
+            } else {
+                #[cfg(feature = "alloc")]
+                remaining.push(pending_garbage.clone());
+                #[cfg(not(feature = "alloc"))]
+                let _ = remaining.push(pending_garbage.clone());
+            }
+        }
+        self.pending_garbages = remaining;
+        ready
+    }
+    pub fn clear(&mut self) {
+        self.pending_garbages.clear();
+    }
+    pub fn get_pending_garbages(&self) -> &[PendingGarbage] {
+        &self.pending_garbages
+    }
+    pub fn get_pending_lines(&self) -> usize {
+        self.pending_garbages.len()
+    }
+    pub fn get_ready_lines(&self) -> usize {
+        self.pending_garbages
+            .iter()
+            .filter(|pending_garbage| pending_garbage.delay == 0)
+            .count()
+    }
+}
+
+//ミノの生成と同じseedを使っても別の乱数列になるようにする
+const GARBAGE_RNG_STREAM: u64 = 1;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum HoleRule {
+    #[default]
+    Clean, //1回の攻撃の中では穴の位置が変わらない
+    Messy(u32), //1ラインごとに指定した確率(%)で穴の位置が変わる
+    Cheese,     //1ラインごとに必ず穴の位置が変わる
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GarbageGenerator {
+    rng: ChaCha20Rng,
+    width: usize,
+    hole_rule: HoleRule,
+    hole_index: Option<usize>,
+}
+impl GarbageGenerator {
+    pub fn new(
+        rng_seed: &impl Hash,
+        width: usize,
+        hole_rule: HoleRule,
+    ) -> Result<Self, GarbageError> {
+        if width == 0 {
+            return Err(GarbageError::InvalidWidth);
+        }
+        let mut rng: ChaCha20Rng = rand_seeder::Seeder::from(rng_seed).into_rng();
+        rng.set_stream(GARBAGE_RNG_STREAM);
+        Ok(Self {
+            rng,
+            width,
+            hole_rule,
+            hole_index: None,
+        })
+    }
+    //1回の攻撃分のラインを生成する
+    pub fn generate(&mut self, lines: usize) -> AttackedLines {
+        let mut attacked_lines = AttackedLines::new();
+        for i in 0..lines {
+            let should_move = match self.hole_rule {
+                HoleRule::Clean => i == 0,
+                HoleRule::Messy(messiness) => {
+                    i == 0 || self.rng.random_ratio(messiness.min(100), 100)
+                }
+                HoleRule::Cheese => true,
+            };
+            let hole_index = match self.hole_index {
+                Some(hole_index) if !should_move => hole_index,
+                _ => self.next_hole_index(),
+            };
+            self.hole_index = Some(hole_index);
+            #[cfg(feature = "alloc")]
+            let hole_indexes = alloc::vec![hole_index];
+            #[cfg(not(feature = "alloc"))]
+            let hole_indexes = {
+                let mut hole_indexes = Vec::new();
+                let _ = hole_indexes.push(hole_index);
+                hole_indexes
+            };
+            let attacked_line = AttackedLine {
+                hole_indexes: Some(hole_indexes),
+                can_be_cleared: true,
+            };
+            #[cfg(feature = "alloc")]
+            attacked_lines.push(attacked_line);
+            #[cfg(not(feature = "alloc"))]
+            let _ = attacked_lines.push(attacked_line);
+        }
+        attacked_lines
+    }
+    //直前と異なる位置を選ぶ
+    fn next_hole_index(&mut self) -> usize {
+        match self.hole_index {
+            Some(hole_index) if self.width > 1 => {
+                (hole_index + self.rng.random_range(1..self.width)) % self.width
+            }
+            _ => self.rng.random_range(0..self.width),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attacked_line(can_be_cleared: bool) -> AttackedLine {
+        AttackedLine {
+            hole_indexes: None,
+            can_be_cleared,
+        }
+    }
+    //各予告のdelayを古い順に返す
+    fn delays(garbage_queue: &GarbageQueue) -> heapless::Vec<usize, 8> {
+        garbage_queue
+            .get_pending_garbages()
+            .iter()
+            .map(|pending_garbage| pending_garbage.delay)
+            .collect()
+    }
+
+    #[test]
+    fn cancel_removes_oldest_first() {
+        let mut garbage_queue = GarbageQueue::new();
+        for delay in 0..3 {
+            garbage_queue.push(attacked_line(true), delay).unwrap();
+        }
+        assert_eq!(garbage_queue.cancel(2), 0);
+        assert_eq!(delays(&garbage_queue), [2]);
+        //相殺しきれなかった分を返す
+        assert_eq!(garbage_queue.cancel(3), 2);
+        assert_eq!(garbage_queue.get_pending_lines(), 0);
+    }
+
+    #[test]
+    fn delay_elapses_per_entry() {
+        let mut garbage_queue = GarbageQueue::new();
+        garbage_queue.push(attacked_line(true), 1).unwrap();
+        garbage_queue.push(attacked_line(true), 3).unwrap();
+        assert_eq!(garbage_queue.get_ready_lines(), 0);
+        garbage_queue.elapse(1);
+        assert_eq!(delays(&garbage_queue), [0, 2]);
+        assert_eq!(garbage_queue.get_ready_lines(), 1);
+        assert_eq!(garbage_queue.pop_ready(None).len(), 1);
+        assert_eq!(delays(&garbage_queue), [2]);
+        garbage_queue.elapse(5);
+        assert_eq!(delays(&garbage_queue), [0]);
+    }
+
+    #[test]
+    fn pop_ready_respects_cap() {
+        let mut garbage_queue = GarbageQueue::new();
+        garbage_queue.push(attacked_line(true), 0).unwrap();
+        garbage_queue.push(attacked_line(false), 1).unwrap();
+        garbage_queue.push(attacked_line(false), 0).unwrap();
+        garbage_queue.push(attacked_line(true), 0).unwrap();
+        //delayが残っているものは飛ばして古い順に取り出す
+        let ready = garbage_queue.pop_ready(Some(2));
+        assert_eq!(ready.len(), 2);
+        assert!(ready[0].can_be_cleared);
+        assert!(!ready[1].can_be_cleared);
+        assert_eq!(delays(&garbage_queue), [1, 0]);
+        assert_eq!(garbage_queue.pop_ready(Some(0)).len(), 0);
+        assert_eq!(garbage_queue.pop_ready(None).len(), 1);
+        assert_eq!(delays(&garbage_queue), [1]);
+    }
+
+    #[test]
+    fn pending_meter() {
+        let mut garbage_queue = GarbageQueue::new();
+        for delay in [0, 2, 0] {
+            garbage_queue.push(attacked_line(true), delay).unwrap();
+        }
+        assert_eq!(garbage_queue.get_pending_lines(), 3);
+        assert_eq!(garbage_queue.get_ready_lines(), 2);
+        garbage_queue.clear();
+        assert_eq!(garbage_queue.get_pending_lines(), 0);
+        assert_eq!(garbage_queue.get_ready_lines(), 0);
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[test]
+    fn push_fails_when_full() {
+        let mut garbage_queue = GarbageQueue::new();
+        for _ in 0..crate::NO_ALLOC_ATTACK_LINE_CAPACITY {
+            garbage_queue.push(attacked_line(true), 0).unwrap();
+        }
+        assert_eq!(
+            garbage_queue.push(attacked_line(true), 0),
+            Err(GarbageError::QueueFull)
+        );
+        assert_eq!(
+            garbage_queue.get_pending_lines(),
+            crate::NO_ALLOC_ATTACK_LINE_CAPACITY
+        );
+    }
+
+    //生成されたラインの穴の位置を順に返す
+    fn holes(garbage_generator: &mut GarbageGenerator, lines: usize) -> heapless::Vec<usize, 256> {
+        garbage_generator
+            .generate(lines)
+            .iter()
+            .map(|attacked_line| attacked_line.hole_indexes.as_ref().unwrap()[0])
+            .collect()
+    }
+    //1回の攻撃の中で穴の位置が変わった回数
+    fn hole_changes(hole_rule: HoleRule, lines: usize) -> usize {
+        let mut garbage_generator = GarbageGenerator::new(&7, 10, hole_rule).unwrap();
+        let holes = holes(&mut garbage_generator, lines);
+        holes.windows(2).filter(|pair| pair[0] != pair[1]).count()
+    }
+
+    #[test]
+    fn generator_rejects_zero_width() {
+        assert_eq!(
+            GarbageGenerator::new(&0, 0, HoleRule::Clean),
+            Err(GarbageError::InvalidWidth)
+        );
+    }
+
+    #[test]
+    fn generator_is_deterministic() {
+        let new =
+            |rng_seed: u64| GarbageGenerator::new(&rng_seed, 10, HoleRule::Messy(50)).unwrap();
+        let (mut a, mut b, mut c) = (new(1), new(1), new(2));
+        let holes_a = holes(&mut a, 100);
+        assert_eq!(holes_a, holes(&mut b, 100));
+        assert_ne!(holes_a, holes(&mut c, 100));
+        assert!(holes_a.iter().all(|&hole_index| hole_index < 10));
+    }
+
+    #[test]
+    fn clean_keeps_hole_within_attack() {
+        let mut garbage_generator = GarbageGenerator::new(&0, 10, HoleRule::Clean).unwrap();
+        let first = holes(&mut garbage_generator, 4);
+        assert!(first.iter().all(|&hole_index| hole_index == first[0]));
+        //次の攻撃では必ず位置が変わる
+        let second = holes(&mut garbage_generator, 4);
+        assert!(second.iter().all(|&hole_index| hole_index == second[0]));
+        assert_ne!(first[0], second[0]);
+    }
+
+    #[test]
+    fn messy_changes_hole_at_given_rate() {
+        assert_eq!(hole_changes(HoleRule::Messy(0), 200), 0);
+        assert_eq!(hole_changes(HoleRule::Messy(100), 200), 199);
+        let changes = hole_changes(HoleRule::Messy(30), 200);
+        assert!((40..80).contains(&changes), "{changes}");
+    }
+
+    #[test]
+    fn cheese_moves_hole_every_line() {
+        assert_eq!(hole_changes(HoleRule::Cheese, 200), 199);
+        //幅が1なら動かせない
+        let mut garbage_generator = GarbageGenerator::new(&0, 1, HoleRule::Cheese).unwrap();
+        assert_eq!(holes(&mut garbage_generator, 3), [0, 0, 0]);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{MovementCommand, RotationType};
+use enum_map::{Enum, EnumMap};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Enum)]
+pub enum Key {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateClockWise,
+    RotateCounterClockWise,
+    Rotate180,
+    Hold,
+}
+//各キーがそのフレームで押されているかどうか
+pub type Buttons = EnumMap<Key, bool>;
+
+//固定時に押されている回転・holdキーを次のミノの出現時に適用するコマンド(IRS/IHS)
+pub(crate) fn initial_commands(buttons: &Buttons) -> heapless::Vec<MovementCommand, 2> {
+    let mut commands = heapless::Vec::new();
+    let rotation_type = [
+        (Key::RotateClockWise, RotationType::Clockwise),
+        (Key::RotateCounterClockWise, RotationType::CounterClockwise),
+        (Key::Rotate180, RotationType::Rotate180),
+    ]
+    .into_iter()
+    .find(|&(key, _)| buttons[key])
+    .map(|(_, rotation_type)| rotation_type);
+    if let Some(rotation_type) = rotation_type {
+        let _ = commands.push(MovementCommand::InitialRotate(rotation_type));
+    }
+    if buttons[Key::Hold] {
+        let _ = commands.push(MovementCommand::InitialHold);
+    }
+    commands
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum DirectionPriority {
+    #[default]
+    LastPressed, //後に押された方を優先
+    FirstPressed, //先に押された方を優先
+    Cancel,       //両方押されていれば動かない
+    Left,
+    Right,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InputConfig {
+    pub das_frames: u32,       //押してから自動移動が始まるまでのフレーム数
+    pub arr_frames: u32,       //自動移動の間隔(0なら壁まで移動する)
+    pub dcd_frames: u32,       //回転・固定の後に自動移動を止めるフレーム数(0なら止めない)
+    pub soft_drop_frames: u32, //ソフトドロップの間隔(0なら接地するまで落下する)
+    pub direction_priority: DirectionPriority,
+    pub is_initial_action_enabled: bool, //ハードドロップ時に押されている回転・holdキーを次のミノに適用する(IRS/IHS)
+}
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            das_frames: 10,
+            arr_frames: 2,
+            dcd_frames: 0,
+            soft_drop_frames: 1,
+            direction_priority: DirectionPriority::default(),
+            is_initial_action_enabled: false,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct InputState {
+    input_config: InputConfig,
+    previous: Buttons,
+    shift_key: Option<Key>,
+    das_counter: u32,
+    soft_drop_counter: u32,
+}
+impl InputState {
+    pub fn new(input_config: InputConfig) -> Self {
+        Self {
+            input_config,
+            ..Default::default()
+        }
+    }
+    //1フレーム分のボタンの状態から生成されたMovementCommandを順にexecuteに渡す
+    //executeは実行に成功したかどうかを返す(壁や床までの自動移動の判定に使われる)
+    pub fn next_frame(
+        &mut self,
+        buttons: &Buttons,
+        mut execute: impl FnMut(MovementCommand) -> bool,
+    ) {
+        let previous = self.previous;
+        let is_pressed = |key: Key| buttons[key] && !previous[key];
+        if is_pressed(Key::Hold) {
+            execute(MovementCommand::Hold);
+        }
+        for (key, command) in [
+            (Key::RotateClockWise, MovementCommand::RotateClockWise),
+            (
+                Key::RotateCounterClockWise,
+                MovementCommand::RotateCounterClockWise,
+            ),
+            (Key::Rotate180, MovementCommand::Rotate180),
+        ] {
+            if is_pressed(key) && execute(command) {
+                self.cut_das();
+            }
+        }
+
+        let shift_key = self.select_shift_key(buttons);
+        if shift_key != self.shift_key {
+            self.shift_key = shift_key;
+            self.das_counter = 0;
+            if let Some(key) = shift_key
+                && is_pressed(key)
+            {
+                execute(Self::shift_command(key));
+            }
+        } else if let Some(key) = shift_key {
+            self.das_counter = self.das_counter.saturating_add(1);
+            let das_frames = self.input_config.das_frames;
+            let arr_frames = self.input_config.arr_frames;
+            if self.das_counter >= das_frames {
+                if arr_frames == 0 {
+                    while execute(Self::shift_command(key)) {}
+                } else if (self.das_counter - das_frames).is_multiple_of(arr_frames) {
+                    execute(Self::shift_command(key));
+                }
+            }
+        }
+
+        if buttons[Key::SoftDrop] {
+            let soft_drop_frames = self.input_config.soft_drop_frames;
+            if is_pressed(Key::SoftDrop) {
+                self.soft_drop_counter = 0;
+            } else {
+                self.soft_drop_counter = self.soft_drop_counter.saturating_add(1);
+            }
+            if soft_drop_frames == 0 {
+                while execute(MovementCommand::Down) {}
+            } else if self.soft_drop_counter.is_multiple_of(soft_drop_frames) {
+                execute(MovementCommand::Down);
+            }
+        }
+        if is_pressed(Key::HardDrop) {
+            if self.input_config.is_initial_action_enabled {
+                for command in initial_commands(buttons) {
+                    execute(command);
+                }
+            }
+            if execute(MovementCommand::HardDrop) {
+                self.cut_das();
+            }
+        }
+        self.previous = *buttons;
+    }
+    //DASが溜まっていればdcd_framesの間だけ自動移動を止める(固定を外部で行ったときにも呼ぶ)
+    pub fn cut_das(&mut self) {
+        if self.shift_key.is_none() || self.input_config.dcd_frames == 0 {
+            return;
+        }
+        let cut = self
+            .input_config
+            .das_frames
+            .saturating_sub(self.input_config.dcd_frames);
+        self.das_counter = self.das_counter.min(cut);
+    }
+    fn select_shift_key(&self, buttons: &Buttons) -> Option<Key> {
+        match (buttons[Key::Left], buttons[Key::Right]) {
+            (false, false) => None,
+            (true, false) => Some(Key::Left),
+            (false, true) => Some(Key::Right),
+            (true, true) => match self.input_config.direction_priority {
+                DirectionPriority::LastPressed => {
+                    match (self.previous[Key::Left], self.previous[Key::Right]) {
+                        (true, false) => Some(Key::Right),
+                        (false, true) => Some(Key::Left),
+                        (true, true) => self.shift_key,
+                        (false, false) => Some(Key::Right), //同時に押されたら右を優先
+                    }
+                }
+                DirectionPriority::FirstPressed => {
+                    match (self.previous[Key::Left], self.previous[Key::Right]) {
+                        (true, false) => Some(Key::Left),
+                        (false, true) => Some(Key::Right),
+                        (true, true) => self.shift_key,
+                        (false, false) => Some(Key::Left), //同時に押されたら左を優先
+                    }
+                }
+                DirectionPriority::Cancel => None,
+                DirectionPriority::Left => Some(Key::Left),
+                DirectionPriority::Right => Some(Key::Right),
+            },
+        }
+    }
+    fn shift_command(key: Key) -> MovementCommand {
+        if key == Key::Left {
+            MovementCommand::Left
+        } else {
+            MovementCommand::Right
+        }
+    }
+    pub fn get_input_config(&self) -> &InputConfig {
+        &self.input_config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //各フレームで押されているキーを順に渡し、フレームごとの横方向の位置を返す(壁は-wallとwall)
+    fn positions<const N: usize>(
+        input_config: InputConfig,
+        frames: [&[Key]; N],
+        wall: i64,
+    ) -> [i64; N] {
+        let mut input_state = InputState::new(input_config);
+        let mut x: i64 = 0;
+        frames.map(|keys| {
+            let buttons = Buttons::from_fn(|key| keys.contains(&key));
+            input_state.next_frame(&buttons, |movement_command| {
+                let next = match movement_command {
+                    MovementCommand::Left => x - 1,
+                    MovementCommand::Right => x + 1,
+                    _ => return true,
+                };
+                let can_move = next.abs() <= wall;
+                if can_move {
+                    x = next;
+                }
+                can_move
+            });
+            x
+        })
+    }
+
+    #[test]
+    fn das_charges_before_arr_repeats() {
+        let input_config = InputConfig {
+            das_frames: 3,
+            arr_frames: 2,
+            ..InputConfig::default()
+        };
+        let left: &[Key] = &[Key::Left];
+        assert_eq!(
+            positions(input_config, [left; 8], 10),
+            [-1, -1, -1, -2, -2, -3, -3, -4]
+        );
+        //離すとDASが溜め直しになる
+        assert_eq!(
+            positions(input_config, [left, left, left, &[], left, left, left], 10),
+            [-1, -1, -1, -1, -2, -2, -2]
+        );
+    }
+
+    #[test]
+    fn arr_zero_moves_to_wall() {
+        let input_config = InputConfig {
+            das_frames: 2,
+            arr_frames: 0,
+            ..InputConfig::default()
+        };
+        let right: &[Key] = &[Key::Right];
+        assert_eq!(positions(input_config, [right; 4], 5), [1, 1, 5, 5]);
+    }
+
+    #[test]
+    fn direction_priority() {
+        let left: &[Key] = &[Key::Left];
+        let both: &[Key] = &[Key::Left, Key::Right];
+        let frames = [left, both, both, both, left];
+        let input_config = |direction_priority| InputConfig {
+            das_frames: 2,
+            arr_frames: 1,
+            direction_priority,
+            ..InputConfig::default()
+        };
+        //後から押した右に切り替わり、右を離すと左のDASを溜め直す
+        assert_eq!(
+            positions(input_config(DirectionPriority::LastPressed), frames, 10),
+            [-1, 0, 0, 1, 1]
+        );
+        //両方押されている間は動かない
+        assert_eq!(
+            positions(input_config(DirectionPriority::Cancel), frames, 10),
+            [-1, -1, -1, -1, -1]
+        );
+        assert_eq!(
+            positions(input_config(DirectionPriority::FirstPressed), frames, 10),
+            [-1, -1, -2, -3, -4]
+        );
+    }
+}
@@ -4,9 +4,45 @@ extern crate alloc;
 use MinoDirection::*;
 use MinoType::*;
 use core::hash::Hash;
-use enum_map::{Enum, EnumMap, enum_map};
-use once_cell::sync::Lazy;
-use rand::seq::SliceRandom;
+use enum_map::{Enum, EnumMap};
+
+mod attack;
+mod board_text;
+mod error;
+mod event;
+#[cfg(feature = "alloc")]
+mod fumen;
+mod garbage;
+mod input;
+mod position;
+mod randomizer;
+#[cfg(feature = "alloc")]
+mod replay;
+#[cfg(feature = "alloc")]
+mod replay_format;
+mod rotation;
+mod scoring;
+#[cfg(feature = "timing")]
+mod timing;
+pub use attack::*;
+pub use board_text::*;
+pub use error::*;
+use event::Events;
+pub use event::{Cells, ClearedRows, Event};
+#[cfg(feature = "alloc")]
+pub use fumen::*;
+pub use garbage::*;
+pub use input::*;
+pub use position::*;
+pub use randomizer::*;
+#[cfg(feature = "alloc")]
+pub use replay::*;
+#[cfg(feature = "alloc")]
+pub use replay_format::REPLAY_FORMAT_VERSION;
+pub use rotation::{Rotate180Kicks, RotationSystem, RotationTable};
+pub use scoring::*;
+#[cfg(feature = "timing")]
+pub use timing::*;
 
 #[cfg(feature = "alloc")]
 use alloc::vec::Vec;
@@ -19,6 +55,12 @@ use serde::{Deserialize, Serialize};
 pub const NO_ALLOC_MINO_QUEUE_CAPACITY: usize = 128;
 #[cfg(not(feature = "alloc"))]
 pub const NO_ALLOC_ATTACK_LINE_CAPACITY: usize = 256;
+#[cfg(not(feature = "alloc"))]
+pub const NO_ALLOC_KICK_CAPACITY: usize = 16;
+#[cfg(not(feature = "alloc"))]
+pub const NO_ALLOC_EVENT_CAPACITY: usize = 32;
+#[cfg(all(feature = "timing", not(feature = "alloc")))]
+pub const NO_ALLOC_LINE_CLEAR_CAPACITY: usize = 16;
 pub const DEFAULT_BOARD_SIZE: (usize, usize) = (10, 42);
 
 #[cfg(feature = "alloc")]
@@ -42,6 +84,16 @@ type FieldWithNextMinoWillSpawn = Vec<Vec<(bool, Cell)>>;
 #[cfg(not(feature = "alloc"))]
 type FieldWithNextMinoWillSpawn =
     Vec<Vec<(bool, Cell), { DEFAULT_BOARD_SIZE.0 }>, { DEFAULT_BOARD_SIZE.1 }>;
+fn to_field_with_next_mino_will_spawn(field: &Field) -> FieldWithNextMinoWillSpawn {
+    let mut r = FieldWithNextMinoWillSpawn::new();
+    for (y, row) in field.iter().enumerate() {
+        r.push(Vec::new());
+        for &cell in row.iter() {
+            r[y].push((false, cell));
+        }
+    }
+    r
+}
 
 #[cfg(feature = "alloc")]
 type NextsField = Vec<MinoType>;
@@ -53,109 +105,9 @@ type AttackedLines = Vec<AttackedLine>;
 #[cfg(not(feature = "alloc"))]
 type AttackedLines = heapless::Vec<AttackedLine, NO_ALLOC_ATTACK_LINE_CAPACITY>;
 
-type OffsetsType = EnumMap<MinoDirection, EnumMap<RotationType, &'static [(i64, i64)]>>;
-#[allow(clippy::complexity)]
-static ROTATIONS: Lazy<EnumMap<MinoType, EnumMap<MinoDirection, [[i64; 4]; 4]>>> =
-    Lazy::new(|| {
-        enum_map! {
-            MinoT => enum_map! {
-                North => [[0, 1, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-                East => [[0, 1, 0, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-                South=>[[0, 0, 0, 0], [1, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-                West=>[[0, 1, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-            },
-            MinoS=>enum_map! {
-                North=>[[0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-                East=>[[0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
-                South=>[[0, 0, 0, 0], [0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
-                West=>[[1, 0, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-            },
-            MinoZ=>enum_map!{
-                North=>[[1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-                East=>[[0, 0, 1, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-                South=>[[0, 0, 0, 0], [1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
-                West=>[[0, 1, 0, 0], [1, 1, 0, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
-            },
-            MinoL=>enum_map! {
-                North=>[[0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-                East=>[[0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
-                South=>[[0, 0, 0, 0], [1, 1, 1, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
-                West=>[[1, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-            },
-            MinoJ=>enum_map!{
-                North=>[[1, 0, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-                East=>[[0, 1, 1, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
-                South=>[[0, 0, 0, 0], [1, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
-                West=>[[0, 1, 0, 0], [0, 1, 0, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
-            },
-            MinoO=>enum_map!{
-                North=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
-                East=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
-                South=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
-                West=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
-            },
-            MinoI=>enum_map!{
-                North=>[[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0]],
-                East=>[[0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0]],
-                South=>[[0, 0, 0, 0], [0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0]],
-                West=>[[0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0]],
-            }
-        }
-    });
-
-static OFFSETS: Lazy<OffsetsType> = Lazy::new(|| {
-    enum_map! {
-        North=>enum_map!{
-                RotationType::Clockwise=>[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)].as_slice(),
-                RotationType::CounterClockwise=>[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)].as_slice(),
-                RotationType::Rotate180=>[(1, 0),(2, 0),(1, 1),(2, 1),(-1, 0),(-2, 0),(-1, 1),(-2, 1),(0, -1),(3, 0),(-3, 0)].as_slice(),
-        },
-        East=>enum_map!{
-                RotationType::Clockwise=>[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)].as_slice(),
-                RotationType::CounterClockwise=>[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)].as_slice(),
-                RotationType::Rotate180=> [(0, 1), (0, 2), (-1, 1), (-1, 2), (0, -1), (0, -2), (-1, -1), (-1, -2), (1, 0), (0, 3), (0, -3),].as_slice(),
-        },
-        South=>enum_map!{
-            RotationType::Clockwise=>[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)].as_slice(),
-            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)].as_slice(),
-            RotationType::Rotate180=>[(-1, 0),(-2, 0),(-1, -1),(-2, -1),(1, 0),(2, 0), (1, -1),(2, -1),(0, 1),(-3, 0),(3, 0)].as_slice(),
-        },
-        West=>enum_map!{
-            RotationType::Clockwise=>[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)].as_slice(),
-            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)].as_slice(),
-            RotationType::Rotate180=>[(0, 1),(0, 2),(1, 1),(1, 2),(0, -1),(0, -2),(1, -1),(1, -2),(-1, 0),(0, 3),(0, -3)].as_slice(),
-        }
-    }
-});
-
-static OFFSETS_MINO_I: Lazy<OffsetsType> = Lazy::new(|| {
-    enum_map! {
-        North=>enum_map!{
-            RotationType::Clockwise=>[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)].as_slice(),
-            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)].as_slice(),
-            RotationType::Rotate180=>[(-1, 0), (-2, 0), (1, 0), (2, 0), (0, 1)].as_slice(),
-        },
-        East=>enum_map!{
-            RotationType::Clockwise=>[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)].as_slice(),
-            RotationType::CounterClockwise=>[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)].as_slice(),
-            RotationType::Rotate180=>[(0, 1), (0, 2), (0, -1), (0, -2), (-1, 0)].as_slice(),
-        },
-        South=>enum_map! {
-            RotationType::Clockwise=>[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)].as_slice(),
-            RotationType::CounterClockwise=>[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)].as_slice(),
-            RotationType::Rotate180=>[(1, 0), (2, 0), (-1, 0), (-2, 0), (0, -1)].as_slice(),
-        },
-        West=>enum_map! {
-            RotationType::Clockwise=> [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)].as_slice(),
-            RotationType::CounterClockwise=>[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)].as_slice(),
-            RotationType::Rotate180=>[(0, 1), (0, 2), (0, -1), (0, -2), (1, 0)].as_slice(),
-        },
-    }
-});
-
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Enum)]
-enum MinoDirection {
+pub enum MinoDirection {
     North,
     East,
     South,
@@ -181,8 +133,9 @@ impl MinoDirection {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Enum)]
-enum RotationType {
+pub enum RotationType {
     Clockwise,        // 時計回り
     Rotate180,        // 180度
     CounterClockwise, // 反時計回り
@@ -200,7 +153,7 @@ pub enum MinoType {
 }
 impl MinoType {
     pub fn get_field_to_draw(&self) -> [[Cell; 4]; 4] {
-        ROTATIONS[*self][North].map(|row| {
+        RotationSystem::Srs.rotations(*self)[North].map(|row| {
             row.map(|cell| {
                 if cell == 0 {
                     Cell::Empty
@@ -220,7 +173,7 @@ enum HorizontalDirection {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "alloc", derive(Eq, PartialEq))]
 #[derive(Clone, Debug)]
-struct MinoQueue {
+struct MinoQueue<R = MinoRandomizer> {
     #[cfg(feature = "alloc")]
     queue: alloc::collections::VecDeque<MinoType>,
     #[cfg(not(feature = "alloc"))]
@@ -228,9 +181,12 @@ struct MinoQueue {
     hold: Option<MinoType>,
     current: MinoType,
     rng: rand_chacha::ChaCha20Rng,
+    randomizer: Option<R>, //Noneなら固定の順番を使い切った時点で終わり
+    preview_count: usize,
+    generated_count: usize, //randomizerが生成したミノの総数
 }
 #[cfg(not(feature = "alloc"))]
-impl core::cmp::PartialEq for MinoQueue {
+impl<R: PartialEq> core::cmp::PartialEq for MinoQueue<R> {
     fn eq(&self, other: &Self) -> bool {
         self.hold == other.hold
             && self
@@ -246,11 +202,14 @@ impl core::cmp::PartialEq for MinoQueue {
             && self.hold == other.hold
             && self.current == other.current
             && self.rng == other.rng
+            && self.randomizer == other.randomizer
+            && self.preview_count == other.preview_count
+            && self.generated_count == other.generated_count
     }
 }
 #[cfg(not(feature = "alloc"))]
-impl core::cmp::Eq for MinoQueue {}
-impl Default for MinoQueue {
+impl<R: Eq> core::cmp::Eq for MinoQueue<R> {}
+impl<R: Default> Default for MinoQueue<R> {
     fn default() -> Self {
         #[cfg(feature = "alloc")]
         let queue = alloc::collections::VecDeque::new();
@@ -262,47 +221,137 @@ impl Default for MinoQueue {
             hold: None,
             queue,
             current: MinoO,
+            randomizer: Some(R::default()),
+            preview_count: 0,
+            generated_count: 0,
         }
     }
 }
-impl MinoQueue {
-    pub fn new(rng_seed: &impl Hash) -> Self {
-        let rng = rand_seeder::Seeder::from(rng_seed).into_rng();
-        let mut mino_queue = Self {
-            rng,
-            ..Default::default()
-        };
-        mino_queue.next();
+impl<R: Randomizer> MinoQueue<R> {
+    pub fn new(rng_seed: &impl Hash, randomizer: R) -> Self {
+        let mut mino_queue = Self::empty(None, rng_seed, Some(randomizer));
+        mino_queue.next(); //randomizerがあるので必ず成功する
         mino_queue
     }
-    pub fn next(&mut self) {
+    pub fn from_sequence(
+        sequence: &[MinoType],
+        hold: Option<MinoType>,
+        rng_seed: &impl Hash,
+        fallback_randomizer: Option<R>,
+    ) -> Result<Self, SequenceError> {
+        let mut mino_queue = Self::empty(hold, rng_seed, fallback_randomizer);
+        for &mino in sequence {
+            #[cfg(feature = "alloc")]
+            mino_queue.queue.push_back(mino);
+            #[cfg(not(feature = "alloc"))]
+            if mino_queue.queue.push_back(mino).is_err() {
+                return Err(SequenceError::TooLong);
+            }
+        }
+        if !mino_queue.next() {
+            return Err(SequenceError::Empty);
+        }
+        Ok(mino_queue)
+    }
+    fn empty(hold: Option<MinoType>, rng_seed: &impl Hash, randomizer: Option<R>) -> Self {
+        #[cfg(feature = "alloc")]
+        let queue = alloc::collections::VecDeque::new();
+
+        #[cfg(not(feature = "alloc"))]
+        let queue = heapless::Deque::new();
+        Self {
+            rng: rand_seeder::Seeder::from(rng_seed).into_rng(),
+            hold,
+            queue,
+            current: MinoO,
+            randomizer,
+            preview_count: 0,
+            generated_count: 0,
+        }
+    }
+    pub fn next(&mut self) -> bool {
         self.generate_if_needed(1);
-        self.current = self.queue.pop_front().unwrap();
+        if let Some(mino) = self.queue.pop_front() {
+            self.current = mino;
+            self.generate_preview();
+            true
+        } else {
+            false
+        }
     }
     pub fn get_next_minos(&mut self, num: usize) -> NextsField {
         self.generate_if_needed(num);
+        self.peek_next_minos(num)
+    }
+    fn peek_next_minos(&self, num: usize) -> NextsField {
         self.queue.iter().copied().take(num).collect()
     }
-    fn hold(&mut self) {
+    //previewの数と現在のbagの残りを常に生成しておく
+    fn generate_preview(&mut self) {
+        self.generate_if_needed(core::cmp::max(self.preview_count, 1));
+        if let Some(bag_size) = self.bag_size() {
+            let bag_end = (self.dealt_count() / bag_size + 1) * bag_size;
+            if bag_end > self.generated_count {
+                self.generate_if_needed(self.queue.len() + bag_end - self.generated_count);
+            }
+        }
+    }
+    fn set_preview_count(&mut self, preview_count: usize) {
+        self.preview_count = preview_count;
+        self.generate_preview();
+    }
+    fn bag_size(&self) -> Option<usize> {
+        self.randomizer
+            .as_ref()
+            .and_then(|randomizer| randomizer.bag_size())
+    }
+    //randomizerが生成したミノのうち、queueから取り出された数
+    fn dealt_count(&self) -> usize {
+        self.generated_count - core::cmp::min(self.queue.len(), self.generated_count)
+    }
+    fn get_bag_state(&self) -> Option<BagState> {
+        let bag_size = self.bag_size()?;
+        let dealt_count = self.dealt_count();
+        let position = dealt_count % bag_size;
+        let mut remaining = EnumMap::default();
+        let first_generated = self.queue.len() + dealt_count - self.generated_count;
+        for &mino in self
+            .queue
+            .iter()
+            .skip(first_generated)
+            .take(bag_size - position)
+        {
+            remaining[mino] += 1;
+        }
+        Some(BagState {
+            bag_size,
+            position,
+            remaining,
+        })
+    }
+    fn hold(&mut self) -> bool {
         if let Some(hold) = self.hold {
             (self.current, self.hold) = (hold, Some(self.current));
         } else {
-            self.hold = Some(self.current);
-            self.next();
+            let current = self.current;
+            if !self.next() {
+                return false;
+            }
+            self.hold = Some(current);
         }
+        true
     }
     fn generate_if_needed(&mut self, required_item_num: usize) {
-        if required_item_num > self.queue.len() {
-            #[cfg(not(feature = "alloc"))]
-            if MINO_ARRAY.len() + self.queue.len() > self.queue.capacity() {
-                panic!("too many!");
-            }
-            let mut mino_array = MINO_ARRAY;
-            mino_array.shuffle(&mut self.rng);
-            for mino in mino_array {
+        #[cfg(not(feature = "alloc"))]
+        if required_item_num > self.queue.capacity() {
+            panic!("too many!");
+        }
+        if let Some(randomizer) = &mut self.randomizer {
+            while required_item_num > self.queue.len() {
+                let mino = randomizer.next_mino(&mut self.rng);
                 self.queue.push_back(mino);
+                self.generated_count += 1;
             }
-            self.generate_if_needed(required_item_num);
         }
     }
     pub fn get_hold(&self) -> Option<MinoType> {
@@ -348,8 +397,8 @@ struct Mino {
     move_reset_count: usize,
     mino_state: MinoState,
     should_be_locked: bool,
-    is_last_move_spin: bool,
-    is_last_move_mini_spin: bool,
+    last_spin: SpinKind,
+    last_kick_index: Option<usize>, //直前の移動が回転なら成功した壁蹴りの番号
 }
 impl Default for Mino {
     fn default() -> Self {
@@ -361,27 +410,40 @@ impl Default for Mino {
             mino_direction: North,
             mino_type: MinoO,
             does_rotate: false,
-            rotation: ROTATIONS[MinoO],
+            rotation: RotationSystem::Srs.rotations(MinoO),
             mino_state: MinoState::AirBorne,
             should_be_locked: false,
-            is_last_move_spin: false,
-            is_last_move_mini_spin: false,
+            last_spin: SpinKind::None,
+            last_kick_index: None,
         }
     }
 }
 
 impl Mino {
-    pub fn new(mino_type: MinoType, x: i64, y: i64, field: &Field) -> Result<Self, ()> {
+    pub fn new(
+        mino_type: MinoType,
+        x: i64,
+        y: i64,
+        field: &Field,
+        rotation_system: &RotationSystem,
+        initial_rotation: Option<RotationType>,
+    ) -> Result<Self, GameOver> {
         let mut template = Self {
             x,
             y,
             maximum_y: y,
             mino_type,
-            rotation: ROTATIONS[mino_type],
+            rotation: rotation_system.rotations(mino_type),
             ..Default::default()
         };
-        if !template.can_replace(x, y, template.mino_direction, field) {
-            return Err(());
+        //IRS: 回転した向きで出現できなければ回転せずに出現する
+        if let Some(rotation_type) = initial_rotation
+            && mino_type != MinoO
+            && template.can_replace(x, y, North.rotate(rotation_type), field)
+        {
+            template.mino_direction = North.rotate(rotation_type);
+        } else if !template.can_replace(x, y, template.mino_direction, field) {
+            return Err(GameOver::BlockOut);
         }
         template.mino_state = if template.can_down(field) {
             MinoState::AirBorne
@@ -396,13 +458,47 @@ impl Mino {
             },
         })
     }
-    fn new_for_preview_next_mino(mino_type: MinoType, x: i64, y: i64) -> Self {
+    //指定した向きと位置に置く(置けなければNone)
+    fn with_direction(
+        mino_type: MinoType,
+        x: i64,
+        y: i64,
+        mino_direction: MinoDirection,
+        field: &Field,
+        rotation_system: &RotationSystem,
+    ) -> Option<Self> {
+        let mut mino = Self {
+            x,
+            y,
+            maximum_y: y,
+            mino_type,
+            mino_direction,
+            does_rotate: mino_type != MinoO,
+            rotation: rotation_system.rotations(mino_type),
+            ..Default::default()
+        };
+        if !mino.can_replace(x, y, mino_direction, field) {
+            return None;
+        }
+        mino.mino_state = if mino.can_down(field) {
+            MinoState::AirBorne
+        } else {
+            MinoState::JustLanded
+        };
+        Some(mino)
+    }
+    fn new_for_preview_next_mino(
+        mino_type: MinoType,
+        x: i64,
+        y: i64,
+        rotation_system: &RotationSystem,
+    ) -> Self {
         Self {
             x,
             y,
             maximum_y: y,
             mino_type,
-            rotation: ROTATIONS[mino_type],
+            rotation: rotation_system.rotations(mino_type),
             ..Default::default()
         }
     }
@@ -444,16 +540,24 @@ impl Mino {
         rotation_type: RotationType,
         field: &Field,
         move_reset_limit: Option<usize>,
+        rotation_system: &RotationSystem,
+        rotate180_kicks: Rotate180Kicks,
+        spin_rule: SpinRule,
     ) -> bool {
-        let mut offsets: &[(i64, i64)] = if self.mino_type == MinoI {
-            OFFSETS_MINO_I[self.mino_direction][rotation_type]
+        let mut offsets = if rotation_type == RotationType::Rotate180 {
+            let Some(offsets) =
+                rotate180_kicks.offsets(rotation_system, self.mino_type, self.mino_direction)
+            else {
+                return false;
+            };
+            offsets
         } else {
-            OFFSETS[self.mino_direction][rotation_type]
+            rotation_system.offsets(self.mino_type, self.mino_direction, rotation_type)
         };
         if !self.does_rotate {
             offsets = &[(0, 0)]; //move_resetとspinの処理だけしたい
         }
-        for offset in offsets.iter() {
+        for (index, offset) in offsets.iter().enumerate() {
             if self.replace(
                 self.x + offset.0,
                 self.y - offset.1,
@@ -461,57 +565,98 @@ impl Mino {
                 field,
                 move_reset_limit,
             ) {
-                if self.mino_type == MinoT {
-                    let center = (self.x + 1, self.y + 1);
-                    let mut corner_count = 0;
-                    for corner_offset_y in [-1, 1] {
-                        for corner_offset_x in [-1, 1] {
-                            let corner = (center.0 + corner_offset_x, center.1 + corner_offset_y);
-                            if self.has_collision(field, corner.0, corner.1) {
-                                corner_count += 1;
-                            }
-                        }
-                    }
-                    if corner_count >= 3 {
-                        self.is_last_move_spin = true;
-
-                        let certain_corner_offsets = match self.mino_direction {
-                            North => [(0, 0), (2, 0)],
-                            East => [(2, 0), (2, 2)],
-                            South => [(0, 2), (2, 2)],
-                            West => [(2, 0), (2, 2)],
-                        };
-                        let mut certain_corner_count = 0;
-                        for certain_corner_offset in certain_corner_offsets {
-                            let certain_corner = (
-                                center.0 + certain_corner_offset.0,
-                                center.1 + certain_corner_offset.1,
-                            );
-                            if self.has_collision(field, certain_corner.0, certain_corner.1) {
-                                certain_corner_count += 1;
-                            }
-                        }
-                        self.is_last_move_mini_spin = certain_corner_count != 2 // t-spin-mini
-                    } else {
-                        self.last_move_is_not_spin();
-                    }
+                let is_last_kick = rotation_type != RotationType::Rotate180
+                    && rotation_system.upgrades_last_kick_t_spin()
+                    && offsets.len() > 1
+                    && index == offsets.len() - 1;
+                self.last_spin = self.detect_spin(field, spin_rule, is_last_kick);
+                self.last_kick_index = Some(index);
+                return true;
+            }
+            if index == 0
+                && rotation_system.has_center_column_rule(self.mino_type)
+                && self
+                    .is_blocked_at_center_column(self.mino_direction.rotate(rotation_type), field)
+            {
+                return false;
+            }
+        }
+        false
+    }
+    fn detect_spin(&self, field: &Field, spin_rule: SpinRule, is_last_kick: bool) -> SpinKind {
+        if self.mino_type == MinoT && spin_rule != SpinRule::AllMini {
+            let spin = self.detect_t_spin(field);
+            //TST,finの壁蹴りはminiにならない
+            if spin == SpinKind::Mini
+                && is_last_kick
+                && matches!(spin_rule, SpinRule::Guideline | SpinRule::Tetrio)
+            {
+                return SpinKind::Full;
+            }
+            return spin;
+        }
+        if matches!(spin_rule, SpinRule::TOnly | SpinRule::Guideline) || !self.is_immobile(field) {
+            return SpinKind::None;
+        }
+        if spin_rule == SpinRule::ImmobileAllSpin {
+            SpinKind::Full
+        } else {
+            SpinKind::Mini
+        }
+    }
+    //3-cornerルール 突起側の角が2つとも埋まっていなければmini
+    fn detect_t_spin(&self, field: &Field) -> SpinKind {
+        let corners = [(0, 0), (2, 0), (0, 2), (2, 2)];
+        let filled_corners: heapless::Vec<(i64, i64), 4> = corners
+            .into_iter()
+            .filter(|corner| self.has_collision(field, self.x + corner.0, self.y + corner.1))
+            .collect();
+        if filled_corners.len() < 3 {
+            return SpinKind::None;
+        }
+        let shape = self.rotation[self.mino_direction];
+        let Some(front) =
+            [(1, 0), (2, 1), (1, 2), (0, 1)]
+                .into_iter()
+                .find(|&(x, y): &(i64, i64)| {
+                    shape[y as usize][x as usize] == 1
+                        && shape[(2 - y) as usize][(2 - x) as usize] == 0
+                })
+        else {
+            return SpinKind::None;
+        };
+        let front_corner_count = filled_corners
+            .iter()
+            .filter(|corner| {
+                if front.0 != 1 {
+                    corner.0 == front.0
                 } else {
-                    let offsets_all_spin = [(0, 1), (0, -1), (1, 0), (-1, 0)];
-                    if offsets_all_spin.iter().all(|offset| {
-                        !self.can_replace(
-                            self.x + offset.0,
-                            self.y + offset.1,
-                            self.mino_direction,
-                            field,
-                        )
-                    }) {
-                        self.is_last_move_spin = true;
-                        self.is_last_move_mini_spin = false;
-                    } else {
-                        self.last_move_is_not_spin();
-                    }
+                    corner.1 == front.1
+                }
+            })
+            .count();
+        if front_corner_count == 2 {
+            SpinKind::Full
+        } else {
+            SpinKind::Mini
+        }
+    }
+    fn is_immobile(&self, field: &Field) -> bool {
+        [(0, 1), (0, -1), (1, 0), (-1, 0)].iter().all(|offset| {
+            !self.can_replace(
+                self.x + offset.0,
+                self.y + offset.1,
+                self.mino_direction,
+                field,
+            )
+        })
+    }
+    fn is_blocked_at_center_column(&self, mino_direction: MinoDirection, field: &Field) -> bool {
+        for (iy, row) in self.rotation[mino_direction].iter().enumerate() {
+            for (ix, &cell) in row.iter().enumerate() {
+                if cell == 1 && self.has_collision(field, self.x + ix as i64, self.y + iy as i64) {
+                    return ix == 1;
                 }
-                return true;
             }
         }
         false
@@ -550,12 +695,12 @@ impl Mino {
             self.move_reset_count = 0;
             self.should_be_locked = false;
         } else {
-            if let Some(move_reset_limit) = move_reset_limit {
-                if move_reset_limit < self.move_reset_count {
-                    self.should_be_locked = true;
-                    if self.mino_state != MinoState::AirBorne {
-                        return false;
-                    }
+            if let Some(move_reset_limit) = move_reset_limit
+                && move_reset_limit < self.move_reset_count
+            {
+                self.should_be_locked = true;
+                if self.mino_state != MinoState::AirBorne {
+                    return false;
                 }
             }
             self.move_reset_count += 1;
@@ -571,6 +716,30 @@ impl Mino {
         };
         true
     }
+    fn cells(&self) -> Cells {
+        let mut cells = Cells::new();
+        for (iy, row) in self.rotation[self.mino_direction].iter().enumerate() {
+            for (ix, &block) in row.iter().enumerate() {
+                if block == 1 {
+                    let _ =
+                        cells.push(((self.x + ix as i64) as usize, (self.y + iy as i64) as usize));
+                }
+            }
+        }
+        cells
+    }
+    //ミノが存在する行の範囲(上端,下端)
+    fn occupied_rows(&self) -> (i64, i64) {
+        let rows = self.rotation[self.mino_direction]
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.contains(&1))
+            .map(|(iy, _)| self.y + iy as i64);
+        (
+            rows.clone().min().unwrap_or(self.y),
+            rows.max().unwrap_or(self.y),
+        )
+    }
     fn lock(&self, field: &mut Field) {
         self.draw(field, Cell::MinoBlock(self.mino_type));
     }
@@ -591,13 +760,7 @@ impl Mino {
         drawer.draw(field, Cell::Ghost(self.mino_type));
     }
     fn draw_next_mino(&self, field: &Field) -> FieldWithNextMinoWillSpawn {
-        let mut r = FieldWithNextMinoWillSpawn::new();
-        for (y, row) in field.iter().enumerate() {
-            r.push(Vec::new());
-            for &cell in row.iter() {
-                r[y].push((false, cell));
-            }
-        }
+        let mut r = to_field_with_next_mino_will_spawn(field);
         for (iy, yy) in (self.y..self.y + 4).enumerate() {
             for (ix, xx) in (self.x..self.x + 4).enumerate() {
                 let yy = yy as usize;
@@ -611,8 +774,8 @@ impl Mino {
         r
     }
     fn last_move_is_not_spin(&mut self) {
-        self.is_last_move_spin = false;
-        self.is_last_move_mini_spin = false;
+        self.last_spin = SpinKind::None;
+        self.last_kick_index = None;
     }
     fn check_mino_status(&mut self) {
         if self.mino_state == MinoState::JustLanded {
@@ -621,7 +784,7 @@ impl Mino {
     }
     fn has_collision(&self, field: &Field, x: i64, y: i64) -> bool {
         if x < 0 || y < 0 {
-            return false;
+            return true;
         }
         let (x, y) = (x as usize, y as usize);
         field
@@ -632,11 +795,18 @@ impl Mino {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct TetrisConfig {
-    move_reset_limit: Option<usize>,
-    appearance_position: (i64, i64), //MinoOのときだけy座標が-1される
-    can_hold_infinity: bool,
-    is_all_spin_enabled: bool,
-    all_spin_considered_as_mini_spin: bool,
+    pub move_reset_limit: Option<usize>,
+    pub appearance_position: (i64, i64), //MinoOのときだけy座標が-1される
+    pub can_hold_infinity: bool,
+    pub spin_rule: SpinRule,
+    pub preview_count: usize,
+    pub rotation_system: RotationSystem,
+    pub rotate180_kicks: Rotate180Kicks,
+    pub garbage_delay: GarbageDelay,
+    pub garbage_cap: Option<usize>, //1回の固定で挿入されるライン数の上限
+    pub is_garbage_blocked_by_line_clear: bool, //ライン消去した固定ではせり上がらない
+    pub visible_height: usize,      //フィールドの下から何行が可視領域か
+    pub lock_out_rule: LockOutRule,
 }
 impl Default for TetrisConfig {
     fn default() -> Self {
@@ -644,26 +814,61 @@ impl Default for TetrisConfig {
             move_reset_limit: Some(15),
             appearance_position: (3, 19),
             can_hold_infinity: false,
-            is_all_spin_enabled: true,
-            all_spin_considered_as_mini_spin: true,
+            spin_rule: SpinRule::Tetrio,
+            preview_count: 5,
+            rotation_system: RotationSystem::Srs,
+            rotate180_kicks: Rotate180Kicks::RotationSystem,
+            garbage_delay: GarbageDelay::default(),
+            garbage_cap: None,
+            is_garbage_blocked_by_line_clear: false,
+            visible_height: 20,
+            lock_out_rule: LockOutRule::Disabled,
         }
     }
 }
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub struct TetrisManager {
+#[derive(Clone, Debug)]
+pub struct TetrisManager<R = MinoRandomizer> {
     width: usize,
     height: usize,
     field: Field,
     tetris_config: TetrisConfig,
-    mino_queue: MinoQueue,
+    mino_queue: MinoQueue<R>,
     current_mino: Mino,
-    attacked_lines_stock: AttackedLines,
+    garbage_queue: GarbageQueue,
     has_held: bool,
+    initial_rotation: Option<RotationType>, //次の出現時に適用される回転(IRS)
+    is_initial_hold_buffered: bool,         //次の出現時にholdする(IHS)
+    game_over: Option<GameOver>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    events: Events, //直前のcommandで発生したイベント
+    #[cfg_attr(feature = "serde", serde(skip))]
+    field_before_line_clear: Option<Field>, //直前の固定でライン消去したなら消去される前のフィールド
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dropped_event_count: usize, //eventsに入りきらなかったイベントの数
     combo: usize,
     b2b: usize,
 }
-impl Default for TetrisManager {
+//直前のcommandの結果(events, field_before_line_clear)は比較しない
+impl<R: PartialEq> PartialEq for TetrisManager<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.field == other.field
+            && self.tetris_config == other.tetris_config
+            && self.mino_queue == other.mino_queue
+            && self.current_mino == other.current_mino
+            && self.garbage_queue == other.garbage_queue
+            && self.has_held == other.has_held
+            && self.initial_rotation == other.initial_rotation
+            && self.is_initial_hold_buffered == other.is_initial_hold_buffered
+            && self.game_over == other.game_over
+            && self.combo == other.combo
+            && self.b2b == other.b2b
+    }
+}
+impl<R: Eq> Eq for TetrisManager<R> {}
+impl<R: Default> Default for TetrisManager<R> {
     fn default() -> Self {
         #[cfg(feature = "alloc")]
         let field =
@@ -678,13 +883,20 @@ impl Default for TetrisManager {
             mino_queue: MinoQueue::default(),
             current_mino: Mino::default(),
             tetris_config,
-            attacked_lines_stock: AttackedLines::new(),
+            garbage_queue: GarbageQueue::new(),
             has_held: false,
+            initial_rotation: None,
+            is_initial_hold_buffered: false,
+            game_over: None,
+            events: Events::new(),
+            field_before_line_clear: None,
+            dropped_event_count: 0,
             combo: 0,
             b2b: 0,
         }
     }
 }
+//7種1巡のbagでミノを生成する(他の方式はwith_randomizerで指定する)
 impl TetrisManager {
     #[cfg(feature = "alloc")]
     pub fn new(
@@ -693,26 +905,127 @@ impl TetrisManager {
         width: usize,
         height: usize,
     ) -> Self {
+        Self::with_randomizer(
+            tetris_config,
+            MinoRandomizer::default(),
+            rng_seed,
+            width,
+            height,
+        )
+    }
+    #[cfg(not(feature = "alloc"))]
+    pub fn new(tetris_config: TetrisConfig, rng_seed: &impl Hash) -> Self {
+        Self::with_randomizer(tetris_config, MinoRandomizer::default(), rng_seed)
+    }
+}
+impl<R: Randomizer> TetrisManager<R> {
+    #[cfg(feature = "alloc")]
+    pub fn with_randomizer(
+        tetris_config: TetrisConfig,
+        randomizer: R,
+        rng_seed: &impl Hash,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        Self::with_mino_queue(
+            tetris_config,
+            MinoQueue::new(rng_seed, randomizer),
+            width,
+            height,
+        )
+    }
+    #[cfg(not(feature = "alloc"))]
+    pub fn with_randomizer(
+        tetris_config: TetrisConfig,
+        randomizer: R,
+        rng_seed: &impl Hash,
+    ) -> Self {
+        Self::with_mino_queue(tetris_config, MinoQueue::new(rng_seed, randomizer))
+    }
+    //sequenceの順にミノが出現し、使い切った後はfallback_randomizerで生成する(Noneならその時点でゲームオーバー)
+    #[cfg(feature = "alloc")]
+    pub fn with_sequence(
+        tetris_config: TetrisConfig,
+        sequence: &[MinoType],
+        hold: Option<MinoType>,
+        fallback_randomizer: Option<R>,
+        rng_seed: &impl Hash,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, SequenceError> {
+        Ok(Self::with_mino_queue(
+            tetris_config,
+            MinoQueue::from_sequence(sequence, hold, rng_seed, fallback_randomizer)?,
+            width,
+            height,
+        ))
+    }
+    #[cfg(not(feature = "alloc"))]
+    pub fn with_sequence(
+        tetris_config: TetrisConfig,
+        sequence: &[MinoType],
+        hold: Option<MinoType>,
+        fallback_randomizer: Option<R>,
+        rng_seed: &impl Hash,
+    ) -> Result<Self, SequenceError> {
+        Ok(Self::with_mino_queue(
+            tetris_config,
+            MinoQueue::from_sequence(sequence, hold, rng_seed, fallback_randomizer)?,
+        ))
+    }
+    #[cfg(feature = "alloc")]
+    fn with_mino_queue(
+        tetris_config: TetrisConfig,
+        mut mino_queue: MinoQueue<R>,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        mino_queue.set_preview_count(tetris_config.preview_count);
         let mut tetris_manager = Self {
             width,
             height,
             field: alloc::vec![alloc::vec![Cell::Empty; width]; height],
             tetris_config,
-            mino_queue: MinoQueue::new(rng_seed),
-            ..Default::default()
+            mino_queue,
+            current_mino: Mino::default(),
+            garbage_queue: GarbageQueue::new(),
+            has_held: false,
+            initial_rotation: None,
+            is_initial_hold_buffered: false,
+            game_over: None,
+            events: Events::new(),
+            field_before_line_clear: None,
+            dropped_event_count: 0,
+            combo: 0,
+            b2b: 0,
         };
-        tetris_manager.spawn_current_mino().unwrap();
+        //出現できなければBlockOutでゲームオーバーになった状態で始まる
+        let _ = tetris_manager.spawn_current_mino();
         tetris_manager
     }
     #[cfg(not(feature = "alloc"))]
-    pub fn new(tetris_config: TetrisConfig, rng_seed: &impl Hash) -> Self {
+    fn with_mino_queue(tetris_config: TetrisConfig, mut mino_queue: MinoQueue<R>) -> Self {
+        mino_queue.set_preview_count(tetris_config.preview_count);
         let mut tetris_manager = Self {
+            width: DEFAULT_BOARD_SIZE.0,
+            height: DEFAULT_BOARD_SIZE.1,
             field: create_empty_field(),
             tetris_config,
-            mino_queue: MinoQueue::new(rng_seed),
-            ..Default::default()
+            mino_queue,
+            current_mino: Mino::default(),
+            garbage_queue: GarbageQueue::new(),
+            has_held: false,
+            initial_rotation: None,
+            is_initial_hold_buffered: false,
+            game_over: None,
+            events: Events::new(),
+            field_before_line_clear: None,
+            dropped_event_count: 0,
+            combo: 0,
+            b2b: 0,
         };
-        tetris_manager.spawn_current_mino().unwrap();
+        //出現できなければBlockOutでゲームオーバーになった状態で始まる
+        let _ = tetris_manager.spawn_current_mino();
         tetris_manager
     }
     pub fn get_field(&self) -> Field {
@@ -721,27 +1034,89 @@ impl TetrisManager {
     pub fn get_field_to_draw(&self) -> Field {
         let mut field = self.field.clone();
         self.current_mino.draw_ghost(&mut field);
-        self.current_mino
-            .draw(&mut field, Cell::MinoInMotion(self.mino_queue.current));
+        self.current_mino.draw(
+            &mut field,
+            Cell::MinoInMotion(self.mino_queue.get_current()),
+        );
         field
     }
-    pub fn get_field_to_draw_with_preview_next_mino(&mut self) -> FieldWithNextMinoWillSpawn {
-        let next_mino_type = self.mino_queue.get_next_minos(1)[0];
+    pub fn get_field_to_draw_with_preview_next_mino(&self) -> FieldWithNextMinoWillSpawn {
+        let Some(&next_mino_type) = self.mino_queue.peek_next_minos(1).first() else {
+            return to_field_with_next_mino_will_spawn(&self.get_field_to_draw());
+        };
         let next_pos = self.get_spawn_point(next_mino_type);
-        let drawer = Mino::new_for_preview_next_mino(next_mino_type, next_pos.0, next_pos.1);
+        let drawer = Mino::new_for_preview_next_mino(
+            next_mino_type,
+            next_pos.0,
+            next_pos.1,
+            &self.tetris_config.rotation_system,
+        );
         drawer.draw_next_mino(&self.get_field_to_draw())
     }
-    fn lock_check(&mut self) -> Option<(Result<MinoState, ()>, Option<LineClear>, bool)> {
+    fn lock_check(&mut self) -> Option<(Result<MinoState, CommandError>, Option<LineClear>, bool)> {
         if self.current_mino.should_be_locked && self.current_mino.mino_state != MinoState::AirBorne
         {
-            return Some(self.command(MovementCommand::Lock));
+            return Some(self.execute_command(MovementCommand::Lock));
         }
         None
     }
+    //戻り値は従来の形のままにし、発生したイベントはget_last_eventsかcommand_with_eventsで受け取る
+    //イベントはcommandを呼ぶたびに消去されるため、直前のコマンドで発生したものだけが残る
     pub fn command(
         &mut self,
         movement_command: MovementCommand,
-    ) -> (Result<MinoState, ()>, Option<LineClear>, bool) {
+    ) -> (Result<MinoState, CommandError>, Option<LineClear>, bool) {
+        self.events.clear();
+        self.dropped_event_count = 0;
+        self.execute_command(movement_command)
+    }
+    //発生したイベントを順にon_eventに渡す
+    pub fn command_with_events(
+        &mut self,
+        movement_command: MovementCommand,
+        mut on_event: impl FnMut(&Event),
+    ) -> (Result<MinoState, CommandError>, Option<LineClear>, bool) {
+        let r = self.command(movement_command);
+        for event in self.events.iter() {
+            on_event(event);
+        }
+        r
+    }
+    pub fn get_last_events(&self) -> &[Event] {
+        &self.events
+    }
+    //no_allocでNO_ALLOC_EVENT_CAPACITYを超えたために直前のcommandで記録されなかったイベントの数
+    pub fn get_dropped_event_count(&self) -> usize {
+        self.dropped_event_count
+    }
+    pub fn get_field_before_line_clear(&self) -> Option<&Field> {
+        self.field_before_line_clear.as_ref()
+    }
+    fn emit(&mut self, event: Event) {
+        #[cfg(feature = "alloc")]
+        self.events.push(event);
+        #[cfg(not(feature = "alloc"))]
+        if self.events.push(event).is_err() {
+            self.dropped_event_count += 1;
+        }
+    }
+    fn execute_command(
+        &mut self,
+        movement_command: MovementCommand,
+    ) -> (Result<MinoState, CommandError>, Option<LineClear>, bool) {
+        if let Some(game_over) = self.game_over {
+            return (Err(CommandError::AfterGameOver(game_over)), None, false);
+        }
+        let is_movement = matches!(
+            movement_command,
+            MovementCommand::Left | MovementCommand::Right | MovementCommand::Down
+        );
+        let is_rotation = matches!(
+            movement_command,
+            MovementCommand::RotateClockWise
+                | MovementCommand::RotateCounterClockWise
+                | MovementCommand::Rotate180
+        );
         let is_succeeded = match movement_command {
             MovementCommand::Left => self.current_mino.horizontal_move(
                 HorizontalDirection::Left,
@@ -758,23 +1133,37 @@ impl TetrisManager {
                 RotationType::Clockwise,
                 &self.field,
                 self.tetris_config.move_reset_limit,
+                &self.tetris_config.rotation_system,
+                self.tetris_config.rotate180_kicks,
+                self.tetris_config.spin_rule,
             ),
             MovementCommand::RotateCounterClockWise => self.current_mino.rotate(
                 RotationType::CounterClockwise,
                 &self.field,
                 self.tetris_config.move_reset_limit,
+                &self.tetris_config.rotation_system,
+                self.tetris_config.rotate180_kicks,
+                self.tetris_config.spin_rule,
             ),
             MovementCommand::Rotate180 => self.current_mino.rotate(
                 RotationType::Rotate180,
                 &self.field,
                 self.tetris_config.move_reset_limit,
+                &self.tetris_config.rotation_system,
+                self.tetris_config.rotate180_kicks,
+                self.tetris_config.spin_rule,
             ),
             MovementCommand::Hold => {
-                if !self.has_held || self.tetris_config.can_hold_infinity {
+                if (!self.has_held || self.tetris_config.can_hold_infinity)
+                    && self.mino_queue.hold()
+                {
                     self.has_held = true;
-                    self.mino_queue.hold();
-                    if self.spawn_current_mino().is_err() {
-                        return (Err(()), None, true);
+                    if let Some(mino_type) = self.mino_queue.get_hold() {
+                        self.emit(Event::Held { mino_type });
+                    }
+                    //バッファされたIRSは手動holdでは消費せず、次の固定後の出現に使う
+                    if let Err(game_over) = self.spawn_mino(None) {
+                        return (Err(CommandError::GameOver(game_over)), None, true);
                     }
                     true
                 } else {
@@ -782,47 +1171,54 @@ impl TetrisManager {
                 }
             }
             MovementCommand::HardDrop => {
-                while self.current_mino.down(&self.field) {}
-                return self.command(MovementCommand::Lock);
+                let mut hard_drop_distance = 0;
+                while self.current_mino.down(&self.field) {
+                    hard_drop_distance += 1;
+                }
+                if hard_drop_distance > 0 {
+                    self.emit(Event::PieceMoved {
+                        x: self.current_mino.x,
+                        y: self.current_mino.y,
+                    });
+                }
+                let mut r = self.execute_command(MovementCommand::Lock);
+                if let Some(line_clear) = &mut r.1 {
+                    line_clear.hard_drop_distance = hard_drop_distance;
+                }
+                return r;
             }
             MovementCommand::Lock => {
+                let is_locked_out = self.is_locked_out();
                 self.current_mino.lock(&mut self.field);
                 self.has_held = false;
+                self.emit(Event::Locked {
+                    mino_type: self.current_mino.mino_type,
+                    cells: self.current_mino.cells(),
+                });
 
-                let mut cleared_line_count = 0;
-                for (y, row) in self.field.clone().iter().enumerate() {
-                    if row.iter().all(|cell| cell.can_be_cleared()) {
-                        self.delete_line(y);
-                        self.current_mino.y += 1; //一緒に落ちる
-                        cleared_line_count += 1;
-                    }
+                let cleared_rows: ClearedRows = self
+                    .field
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row)| row.iter().all(|cell| cell.can_be_cleared()))
+                    .map(|(y, _)| y)
+                    .collect();
+                let cleared_line_count = cleared_rows.len();
+                //ライン消去しないならフィールドを複製しない
+                self.field_before_line_clear = (cleared_line_count > 0).then(|| self.field.clone());
+                for &y in cleared_rows.iter() {
+                    self.delete_line(y);
+                    self.current_mino.y += 1; //一緒に落ちる
                 }
-                let is_spin = if self.current_mino.mino_type == MinoT {
-                    self.current_mino.is_last_move_spin
-                } else {
-                    if self.tetris_config.is_all_spin_enabled
-                        && !self.tetris_config.all_spin_considered_as_mini_spin
-                    {
-                        self.current_mino.is_last_move_spin
-                    } else {
-                        false
-                    }
-                };
-
-                let is_spin_mini = if self.current_mino.mino_type == MinoT {
-                    self.current_mino.is_last_move_mini_spin
-                } else {
-                    if self.tetris_config.is_all_spin_enabled
-                        && self.tetris_config.all_spin_considered_as_mini_spin
-                    {
-                        self.current_mino.is_last_move_spin
-                    } else {
-                        false
-                    }
-                };
+                if cleared_line_count > 0 {
+                    self.emit(Event::LinesCleared {
+                        rows: cleared_rows.clone(),
+                    });
+                }
+                let spin = self.current_mino.last_spin;
                 if cleared_line_count > 0 {
                     self.combo += 1;
-                    if is_spin_mini || is_spin {
+                    if spin != SpinKind::None || cleared_line_count >= 4 {
                         self.b2b += 1;
                     } else {
                         self.b2b = 0;
@@ -839,35 +1235,156 @@ impl TetrisManager {
                         .iter()
                         .all(|row| row.iter().all(|cell| !cell.has_collision())),
                     mino_type: self.current_mino.mino_type,
-                    is_spin,
-                    is_spin_mini,
+                    spin,
+                    kick_index: self.current_mino.last_kick_index,
+                    hard_drop_distance: 0,
+                    cleared_rows,
                 };
-                self.mino_queue.next();
+                let has_next = self.mino_queue.next();
 
-                self.release_stock_attacked_line();
-                return if self.spawn_current_mino().is_err() {
-                    (Err(()), Some(line_clear), true)
+                if let GarbageDelay::Pieces(_) = self.tetris_config.garbage_delay {
+                    self.garbage_queue.elapse(1);
+                }
+                //ライン消去した場合はCancelGarbageでそのミノでの攻撃による相殺を待つ
+                let released = if is_locked_out {
+                    Err(self.end_game(GameOver::LockOut))
+                } else if self.tetris_config.is_garbage_blocked_by_line_clear
+                    && cleared_line_count > 0
+                {
+                    Ok(())
                 } else {
-                    (Ok(self.current_mino.mino_state), Some(line_clear), true)
+                    self.release_stock_attacked_line()
+                };
+                let spawned = released.and_then(|_| {
+                    if !has_next {
+                        return Err(self.end_game(GameOver::OutOfMinos));
+                    }
+                    if core::mem::take(&mut self.is_initial_hold_buffered) && self.mino_queue.hold()
+                    {
+                        self.has_held = true;
+                        if let Some(mino_type) = self.mino_queue.get_hold() {
+                            self.emit(Event::Held { mino_type });
+                        }
+                    }
+                    self.spawn_current_mino()
+                });
+                return match spawned {
+                    Ok(()) => (Ok(self.current_mino.mino_state), Some(line_clear), true),
+                    Err(game_over) => (
+                        Err(CommandError::GameOver(game_over)),
+                        Some(line_clear),
+                        true,
+                    ),
                 };
             }
             MovementCommand::Attacked(attacked_line) => {
-                self.attacked_lines_stock.push(attacked_line);
+                if let Some(hole_indexes) = &attacked_line.hole_indexes
+                    && let Some(&hole_index) = hole_indexes.iter().find(|&&i| i >= self.width)
+                {
+                    return (
+                        Err(CommandError::HoleIndexOutOfRange(hole_index)),
+                        None,
+                        false,
+                    );
+                }
+                let delay = match self.tetris_config.garbage_delay {
+                    GarbageDelay::Pieces(delay) | GarbageDelay::Frames(delay) => delay,
+                };
+                if let Err(garbage_error) = self.garbage_queue.push(attacked_line, delay) {
+                    return (Err(CommandError::Garbage(garbage_error)), None, false);
+                }
+                true
+            }
+            MovementCommand::CancelGarbage(lines) => self.garbage_queue.cancel(lines) < lines,
+            MovementCommand::ElapseGarbageFrames(frames) => {
+                if let GarbageDelay::Frames(_) = self.tetris_config.garbage_delay {
+                    self.garbage_queue.elapse(frames);
+                    true
+                } else {
+                    false
+                }
+            }
+            MovementCommand::InitialRotate(rotation_type) => {
+                self.initial_rotation = Some(rotation_type);
+                true
+            }
+            MovementCommand::InitialHold => {
+                self.is_initial_hold_buffered = true;
                 true
             }
         };
+        if is_succeeded && is_movement {
+            self.emit(Event::PieceMoved {
+                x: self.current_mino.x,
+                y: self.current_mino.y,
+            });
+        }
+        if is_succeeded && is_rotation {
+            self.emit(Event::Rotated {
+                direction: self.current_mino.mino_direction,
+                kick_index: self.current_mino.last_kick_index.unwrap_or(0),
+            });
+            if self.current_mino.last_spin != SpinKind::None {
+                self.emit(Event::Spun {
+                    mino_type: self.current_mino.mino_type,
+                    spin: self.current_mino.last_spin,
+                });
+            }
+        }
         if let Some(r) = self.lock_check() {
             return r;
         }
         (Ok(self.current_mino.mino_state), None, is_succeeded)
     }
-    pub fn spawn_current_mino(&mut self) -> Result<(), ()> {
+    pub fn spawn_current_mino(&mut self) -> Result<(), GameOver> {
+        let initial_rotation = self.initial_rotation.take();
+        self.spawn_mino(initial_rotation)
+    }
+    fn spawn_mino(&mut self, initial_rotation: Option<RotationType>) -> Result<(), GameOver> {
         let next_pos = self.get_spawn_point(self.mino_queue.current);
-        self.current_mino =
-            Mino::new(self.mino_queue.current, next_pos.0, next_pos.1, &self.field)?;
+        self.current_mino = Mino::new(
+            self.mino_queue.current,
+            next_pos.0,
+            next_pos.1,
+            &self.field,
+            &self.tetris_config.rotation_system,
+            initial_rotation,
+        )
+        .map_err(|game_over| self.end_game(game_over))?;
+        self.emit(Event::Spawned {
+            mino_type: self.current_mino.mino_type,
+            x: self.current_mino.x,
+            y: self.current_mino.y,
+            direction: self.current_mino.mino_direction,
+        });
         Ok(())
     }
+    fn is_locked_out(&self) -> bool {
+        let visible_top = self.get_visible_top() as i64;
+        let (top, bottom) = self.current_mino.occupied_rows();
+        match self.tetris_config.lock_out_rule {
+            LockOutRule::Disabled => false,
+            LockOutRule::LockOut => bottom < visible_top,
+            LockOutRule::PartialLockOut => top < visible_top,
+        }
+    }
+    //可視領域の一番上の行
+    pub fn get_visible_top(&self) -> usize {
+        self.height
+            .saturating_sub(self.tetris_config.visible_height)
+    }
+    fn end_game(&mut self, game_over: GameOver) -> GameOver {
+        self.game_over = Some(game_over);
+        self.emit(Event::GameOver(game_over));
+        game_over
+    }
+    pub fn get_game_over(&self) -> Option<GameOver> {
+        self.game_over
+    }
 
+    pub fn get_last_kick_index(&self) -> Option<usize> {
+        self.current_mino.last_kick_index
+    }
     pub fn get_mino_state(&mut self) -> MinoState {
         self.current_mino.check_mino_status();
         self.current_mino.mino_state
@@ -889,8 +1406,12 @@ impl TetrisManager {
             };
         }
     }
-    pub fn release_stock_attacked_line(&mut self) {
-        for attacked_line in &self.attacked_lines_stock {
+    pub fn release_stock_attacked_line(&mut self) -> Result<(), GameOver> {
+        let mut is_topped_out = false;
+        let attacked_lines = self.garbage_queue.pop_ready(self.tetris_config.garbage_cap);
+        for attacked_line in &attacked_lines {
+            //一番上の行にブロックがあればフィールドの外に押し出される
+            is_topped_out |= self.field[0].iter().any(|cell| cell.has_collision());
             self.field.rotate_left(1);
             #[cfg(feature = "alloc")]
             let mut inserted_line =
@@ -910,13 +1431,35 @@ impl TetrisManager {
             }
             *self.field.last_mut().unwrap() = inserted_line;
         }
-        self.attacked_lines_stock.clear();
+        if !attacked_lines.is_empty() {
+            self.emit(Event::GarbageInserted {
+                lines: attacked_lines.len(),
+            });
+        }
+        if is_topped_out {
+            return Err(self.end_game(GameOver::TopOut));
+        }
+        Ok(())
+    }
+    pub fn get_garbage_queue(&self) -> &GarbageQueue {
+        &self.garbage_queue
+    }
+    pub fn get_pending_garbage_lines(&self) -> usize {
+        self.garbage_queue.get_pending_lines()
     }
     pub fn get_next_minos(&mut self, num: usize) -> NextsField {
         self.mino_queue.get_next_minos(num)
     }
+    //tetris_configのpreview_count個までは常に生成済み
+    pub fn get_preview_minos(&self) -> NextsField {
+        self.mino_queue
+            .peek_next_minos(self.tetris_config.preview_count)
+    }
+    pub fn get_bag_state(&self) -> Option<BagState> {
+        self.mino_queue.get_bag_state()
+    }
     pub fn get_hold_mino(&self) -> Option<MinoType> {
-        self.mino_queue.hold
+        self.mino_queue.get_hold()
     }
     pub fn get_spawn_point(&self, mino_type: MinoType) -> (i64, i64) {
         (
@@ -954,18 +1497,73 @@ pub enum MovementCommand {
     Lock,
     HardDrop,
     Attacked(AttackedLine),
+    InitialRotate(RotationType), //次のミノの出現時に回転する(IRS)
+    InitialHold,                 //次のミノの出現時にholdする(IHS)
+    CancelGarbage(usize), //攻撃のライン数だけ古い予告から相殺する(残りはget_pending_garbage_linesで分かる)
+    ElapseGarbageFrames(usize), //GarbageDelay::Framesの予告の待ち時間を進める
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SpinKind {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub enum SpinRule {
+    TOnly,           //Tミノのみ(3-corner)
+    Guideline,       //Tミノのみ(3-corner, 最後の壁蹴りならminiでなくなる)
+    ImmobileAllSpin, //T以外も動けなくなる回転ならspin
+    AllMini,         //全てのミノで動けなくなる回転ならmini
+    Tetrio,          //Tミノは3-corner, T以外は動けなくなる回転ならmini
+}
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Default)]
+//既定のappearance_positionは42行のフィールドで可視領域(下から20行)より上にあるため、
+//LockOutでは出現位置から2行以上落とさずに固定するとゲームオーバーになる
+//出現位置のまま固定できるようにするならvisible_heightかappearance_positionを合わせる
+pub enum LockOutRule {
+    #[default]
+    Disabled,
+    LockOut,        //ミノ全体が可視領域より上で固定されたらゲームオーバー
+    PartialLockOut, //ミノの一部でも可視領域より上で固定されたらゲームオーバー
+}
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LineClear {
-    cleared_line_count: usize,
-    combo: usize,
-    b2b: usize,
-    is_perfect: bool,
-    mino_type: MinoType,
-    is_spin: bool,
-    is_spin_mini: bool,
+    pub cleared_line_count: usize,
+    pub combo: usize,
+    pub b2b: usize,
+    pub is_perfect: bool,
+    pub mino_type: MinoType,
+    pub spin: SpinKind,
+    pub kick_index: Option<usize>,
+    pub hard_drop_distance: usize, //hard dropで落下したマス数
+    pub cleared_rows: ClearedRows, //消去される前の行の番号
+}
+impl LineClear {
+    //b2bが継続するライン消去(spinもしくは4ライン消去)
+    pub fn is_difficult(&self) -> bool {
+        self.cleared_line_count >= 4 || (self.cleared_line_count > 0 && self.spin != SpinKind::None)
+    }
+}
+//得点や攻撃の計算のテストで使うTミノのライン消去
+#[cfg(test)]
+pub(crate) fn line_clear(lines: usize, spin: SpinKind, combo: usize, b2b: usize) -> LineClear {
+    LineClear {
+        cleared_line_count: lines,
+        combo,
+        b2b,
+        is_perfect: false,
+        mino_type: MinoT,
+        spin,
+        kick_index: None,
+        hard_drop_distance: 0,
+        cleared_rows: ClearedRows::new(),
+    }
 }
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -976,3 +1574,303 @@ pub struct AttackedLine {
     pub hole_indexes: Option<Vec<usize, { DEFAULT_BOARD_SIZE.0 }>>,
     pub can_be_cleared: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_sequence(tetris_config: TetrisConfig, sequence: &[MinoType]) -> TetrisManager {
+        #[cfg(feature = "alloc")]
+        let tetris_manager = TetrisManager::with_sequence(
+            tetris_config,
+            sequence,
+            None,
+            None,
+            &0,
+            DEFAULT_BOARD_SIZE.0,
+            DEFAULT_BOARD_SIZE.1,
+        );
+        #[cfg(not(feature = "alloc"))]
+        let tetris_manager = TetrisManager::with_sequence(tetris_config, sequence, None, None, &0);
+        tetris_manager.unwrap()
+    }
+
+    #[test]
+    fn initial_rotation_applies_to_next_mino() {
+        let mut tetris_manager = with_sequence(TetrisConfig::default(), &[MinoT; 3]);
+        let _ = tetris_manager.command(MovementCommand::InitialRotate(RotationType::Clockwise));
+        assert_eq!(tetris_manager.current_mino.mino_direction, North);
+        let _ = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(tetris_manager.current_mino.mino_direction, East);
+        let _ = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(tetris_manager.current_mino.mino_direction, North);
+    }
+
+    #[test]
+    fn initial_rotation_saves_block_out() {
+        //北向きでは重なり、東向きでは重ならない位置にブロックを置く
+        let tetris_config = TetrisConfig::default();
+        let (x, y) = tetris_config.appearance_position;
+        let rotation_system = &tetris_config.rotation_system;
+        let field = with_sequence(tetris_config.clone(), &[MinoT]).get_field();
+        let north = Mino::new(MinoT, x, y, &field, rotation_system, None).unwrap();
+        let east = Mino::new(
+            MinoT,
+            x,
+            y,
+            &field,
+            rotation_system,
+            Some(RotationType::Clockwise),
+        )
+        .unwrap();
+        let east_cells = east.cells();
+        let &(block_x, block_y) = north
+            .cells()
+            .iter()
+            .find(|cell| !east_cells.contains(cell))
+            .unwrap();
+
+        let block_out = |initial_rotation: Option<RotationType>| {
+            let mut tetris_manager = with_sequence(tetris_config.clone(), &[MinoT; 3]);
+            //最初のミノはブロックの無い列に落とす
+            for _ in 0..3 {
+                let _ = tetris_manager.command(MovementCommand::Right);
+            }
+            tetris_manager.field[block_y][block_x] = Cell::Obstruction(false);
+            if let Some(rotation_type) = initial_rotation {
+                let _ = tetris_manager.command(MovementCommand::InitialRotate(rotation_type));
+            }
+            let (r, _, _) = tetris_manager.command(MovementCommand::HardDrop);
+            r
+        };
+        assert_eq!(
+            block_out(None),
+            Err(CommandError::GameOver(GameOver::BlockOut))
+        );
+        assert_eq!(
+            block_out(Some(RotationType::Clockwise)),
+            Ok(MinoState::AirBorne)
+        );
+    }
+
+    #[test]
+    fn initial_rotation_is_kept_across_manual_hold() {
+        let mut tetris_manager = with_sequence(TetrisConfig::default(), &[MinoT, MinoJ, MinoL]);
+        let _ = tetris_manager.command(MovementCommand::InitialRotate(RotationType::Clockwise));
+        let _ = tetris_manager.command(MovementCommand::Hold);
+        assert_eq!(tetris_manager.current_mino.mino_type, MinoJ);
+        assert_eq!(tetris_manager.current_mino.mino_direction, North);
+        let _ = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(tetris_manager.current_mino.mino_type, MinoL);
+        assert_eq!(tetris_manager.current_mino.mino_direction, East);
+    }
+
+    #[test]
+    fn initial_hold_holds_next_mino() {
+        let mut tetris_manager = with_sequence(TetrisConfig::default(), &[MinoT, MinoJ, MinoL]);
+        let _ = tetris_manager.command(MovementCommand::InitialHold);
+        let _ = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(tetris_manager.get_hold_mino(), Some(MinoJ));
+        assert_eq!(tetris_manager.current_mino.mino_type, MinoL);
+        //IHSでholdしたミノも固定するまで再度holdできない
+        let (_, _, is_succeeded) = tetris_manager.command(MovementCommand::Hold);
+        assert!(!is_succeeded);
+    }
+
+    #[test]
+    fn initial_hold_is_not_applied_after_game_over() {
+        let tetris_config = TetrisConfig {
+            lock_out_rule: LockOutRule::LockOut,
+            ..TetrisConfig::default()
+        };
+        let mut tetris_manager = with_sequence(tetris_config, &[MinoT, MinoJ, MinoL]);
+        let _ = tetris_manager.command(MovementCommand::InitialHold);
+        //出現位置のまま固定すると可視領域より上なのでロックアウトになる
+        let (r, _, _) = tetris_manager.command(MovementCommand::Lock);
+        assert_eq!(r, Err(CommandError::GameOver(GameOver::LockOut)));
+        assert_eq!(tetris_manager.get_hold_mino(), None);
+    }
+
+    fn garbage_line() -> MovementCommand {
+        MovementCommand::Attacked(AttackedLine {
+            hole_indexes: None,
+            can_be_cleared: true,
+        })
+    }
+
+    #[test]
+    fn garbage_commands() {
+        let tetris_config = TetrisConfig {
+            garbage_delay: GarbageDelay::Frames(2),
+            ..TetrisConfig::default()
+        };
+        let mut tetris_manager = with_sequence(tetris_config, &[MinoT; 3]);
+        for _ in 0..3 {
+            let _ = tetris_manager.command(garbage_line());
+        }
+        let (_, _, is_succeeded) = tetris_manager.command(MovementCommand::CancelGarbage(1));
+        assert!(is_succeeded);
+        assert_eq!(tetris_manager.get_pending_garbage_lines(), 2);
+        let _ = tetris_manager.command(MovementCommand::ElapseGarbageFrames(1));
+        assert_eq!(tetris_manager.get_garbage_queue().get_ready_lines(), 0);
+        let _ = tetris_manager.command(MovementCommand::ElapseGarbageFrames(1));
+        assert_eq!(tetris_manager.get_garbage_queue().get_ready_lines(), 2);
+        let _ = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(tetris_manager.get_pending_garbage_lines(), 0);
+        let (_, _, is_succeeded) = tetris_manager.command(MovementCommand::CancelGarbage(1));
+        assert!(!is_succeeded);
+    }
+
+    #[test]
+    fn garbage_is_not_blocked_by_line_clear_by_default() {
+        let tetris_config = TetrisConfig::default();
+        assert!(!tetris_config.is_garbage_blocked_by_line_clear);
+        let mut tetris_manager = with_sequence(tetris_config, &[MinoI; 3]);
+        //Iミノ以外の列を埋めておき、1ライン消去する
+        let bottom = tetris_manager.height - 1;
+        for x in (0..tetris_manager.width).filter(|x| !(3..7).contains(x)) {
+            tetris_manager.field[bottom][x] = Cell::Obstruction(true);
+        }
+        let _ = tetris_manager.command(garbage_line());
+        let (_, line_clear, _) = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(line_clear.unwrap().cleared_line_count, 1);
+        assert_eq!(tetris_manager.get_pending_garbage_lines(), 0);
+    }
+
+    #[test]
+    fn lock_out_rules() {
+        //出現位置から落とした行数ごとにゲームオーバーになったかを返す
+        let is_locked_out = |lock_out_rule: LockOutRule| {
+            [0, 1, 2, 3].map(|downs| {
+                let tetris_config = TetrisConfig {
+                    lock_out_rule,
+                    ..TetrisConfig::default()
+                };
+                let mut tetris_manager = with_sequence(tetris_config, &[MinoT; 2]);
+                for _ in 0..downs {
+                    let _ = tetris_manager.command(MovementCommand::Down);
+                }
+                let (r, _, _) = tetris_manager.command(MovementCommand::Lock);
+                r == Err(CommandError::GameOver(GameOver::LockOut))
+            })
+        };
+        //Tミノは出現時に19,20行目にあり、可視領域は22行目から
+        assert_eq!(is_locked_out(LockOutRule::Disabled), [false; 4]);
+        assert_eq!(
+            is_locked_out(LockOutRule::LockOut),
+            [true, true, false, false]
+        );
+        assert_eq!(
+            is_locked_out(LockOutRule::PartialLockOut),
+            [true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn lock_out_follows_visible_height() {
+        let tetris_config = TetrisConfig {
+            lock_out_rule: LockOutRule::LockOut,
+            visible_height: 23,
+            ..TetrisConfig::default()
+        };
+        let mut tetris_manager = with_sequence(tetris_config, &[MinoT; 2]);
+        assert_eq!(tetris_manager.get_visible_top(), 19);
+        //次のミノの出現を妨げないように左に寄せて出現した高さのまま固定する
+        for _ in 0..3 {
+            let _ = tetris_manager.command(MovementCommand::Left);
+        }
+        let (r, _, _) = tetris_manager.command(MovementCommand::Lock);
+        assert_eq!(r, Ok(MinoState::AirBorne));
+    }
+
+    #[test]
+    fn events_for_lock_clear_and_spawn() {
+        let mut tetris_manager = with_sequence(TetrisConfig::default(), &[MinoI, MinoT, MinoO]);
+        let bottom = tetris_manager.height - 1;
+        for x in (0..tetris_manager.width).filter(|x| !(3..7).contains(x)) {
+            tetris_manager.field[bottom][x] = Cell::Obstruction(true);
+        }
+        let (_, line_clear, _) = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(line_clear.unwrap().cleared_rows.as_slice(), [bottom]);
+        let cells: Cells = (3..7).map(|x| (x, bottom)).collect();
+        assert_eq!(
+            tetris_manager.get_last_events(),
+            [
+                Event::PieceMoved {
+                    x: 3,
+                    y: bottom as i64 - 1,
+                },
+                Event::Locked {
+                    mino_type: MinoI,
+                    cells,
+                },
+                Event::LinesCleared {
+                    rows: [bottom].into_iter().collect(),
+                },
+                Event::Spawned {
+                    mino_type: MinoT,
+                    x: 3,
+                    y: 19,
+                    direction: North,
+                },
+            ]
+        );
+        //失敗したコマンドでも直前のイベントは消去される
+        let _ = tetris_manager.command(MovementCommand::Right);
+        assert_eq!(
+            tetris_manager.get_last_events(),
+            [Event::PieceMoved { x: 4, y: 19 }]
+        );
+        for _ in 0..5 {
+            let _ = tetris_manager.command(MovementCommand::Right);
+        }
+        assert_eq!(tetris_manager.get_last_events(), []);
+    }
+
+    #[test]
+    fn events_for_hold_garbage_and_game_over() {
+        let mut tetris_manager = with_sequence(TetrisConfig::default(), &[MinoT, MinoJ, MinoL]);
+        let mut events = heapless::Vec::<Event, 8>::new();
+        let _ = tetris_manager.command_with_events(MovementCommand::Hold, |event| {
+            let _ = events.push(event.clone());
+        });
+        assert_eq!(
+            events,
+            [
+                Event::Held { mino_type: MinoT },
+                Event::Spawned {
+                    mino_type: MinoJ,
+                    x: 3,
+                    y: 19,
+                    direction: North,
+                },
+            ]
+        );
+
+        let _ = tetris_manager.command(garbage_line());
+        assert_eq!(tetris_manager.get_last_events(), []);
+        //せり上がった後に次のミノの出現位置を塞ぐブロックを置き、その列を避けて落とす
+        tetris_manager.field[21][4] = Cell::Obstruction(true);
+        for _ in 0..3 {
+            let _ = tetris_manager.command(MovementCommand::Left);
+        }
+        let (r, _, _) = tetris_manager.command(MovementCommand::HardDrop);
+        assert_eq!(r, Err(CommandError::GameOver(GameOver::BlockOut)));
+        let events = tetris_manager.get_last_events();
+        assert!(matches!(events[0], Event::PieceMoved { x: 0, .. }));
+        assert!(matches!(
+            events[1],
+            Event::Locked {
+                mino_type: MinoJ,
+                ..
+            }
+        ));
+        assert_eq!(
+            events[2..],
+            [
+                Event::GarbageInserted { lines: 1 },
+                Event::GameOver(GameOver::BlockOut),
+            ]
+        );
+    }
+}
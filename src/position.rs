@@ -0,0 +1,277 @@
+use crate::{
+    Cell, Event, Field, MINO_ARRAY, Mino, MinoDirection, MinoQueue, MinoRandomizer, MinoType,
+    NextsField, PositionError, TetrisConfig, TetrisManager,
+};
+use core::hash::Hash;
+
+#[cfg(not(feature = "alloc"))]
+use crate::{DEFAULT_BOARD_SIZE, NO_ALLOC_MINO_QUEUE_CAPACITY, SequenceError};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub field: Field, //allocが使えるならフィールドのサイズはこのfieldのサイズになる
+    pub current: MinoType,
+    pub direction: MinoDirection,
+    pub location: Option<(i64, i64)>, //4x4の枠の位置(Noneなら出現位置)
+    pub hold: Option<MinoType>,
+    pub has_held: bool,
+    pub queue: NextsField,                  //currentの次に出現するミノから順に
+    pub randomizer: Option<MinoRandomizer>, //queueを使い切った後に使う(Noneならその時点でゲームオーバー)
+    pub combo: usize,
+    pub b2b: usize,
+}
+impl Position {
+    pub fn new(field: Field, current: MinoType) -> Self {
+        Self {
+            field,
+            current,
+            direction: MinoDirection::North,
+            location: None,
+            hold: None,
+            has_held: false,
+            queue: NextsField::new(),
+            randomizer: Some(MinoRandomizer::default()),
+            combo: 0,
+            b2b: 0,
+        }
+    }
+    fn validate(&self) -> Result<(), PositionError> {
+        let width = self.field.first().map_or(0, |row| row.len());
+        #[cfg(not(feature = "alloc"))]
+        if width != DEFAULT_BOARD_SIZE.0 || self.field.len() != DEFAULT_BOARD_SIZE.1 {
+            return Err(PositionError::InvalidFieldSize);
+        }
+        if width == 0 || self.field.iter().any(|row| row.len() != width) {
+            return Err(PositionError::InvalidFieldSize);
+        }
+        for (y, row) in self.field.iter().enumerate() {
+            if let Some(x) = row
+                .iter()
+                .position(|cell| matches!(cell, Cell::MinoInMotion(_) | Cell::Ghost(_)))
+            {
+                return Err(PositionError::InvalidCell { x, y });
+            }
+        }
+        if let Some(y) = self
+            .field
+            .iter()
+            .position(|row| row.iter().all(|cell| cell.can_be_cleared()))
+        {
+            return Err(PositionError::FullRow { y });
+        }
+        if self.has_held && self.hold.is_none() {
+            return Err(PositionError::HeldWithoutHold);
+        }
+        #[cfg(not(feature = "alloc"))]
+        if self.queue.len() >= NO_ALLOC_MINO_QUEUE_CAPACITY {
+            return Err(PositionError::Sequence(SequenceError::TooLong));
+        }
+        Ok(())
+    }
+}
+
+impl TetrisManager {
+    pub fn from_position(
+        tetris_config: TetrisConfig,
+        position: Position,
+        rng_seed: &impl Hash,
+    ) -> Result<Self, PositionError> {
+        position.validate()?;
+        let mut sequence = NextsField::new();
+        #[cfg(feature = "alloc")]
+        {
+            sequence.push(position.current);
+            sequence.extend_from_slice(&position.queue);
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = sequence.push(position.current);
+            let _ = sequence.extend_from_slice(&position.queue);
+        }
+        let mino_queue =
+            MinoQueue::from_sequence(&sequence, position.hold, rng_seed, position.randomizer)
+                .map_err(PositionError::Sequence)?;
+        #[cfg(feature = "alloc")]
+        let mut tetris_manager = Self::with_mino_queue(
+            tetris_config,
+            mino_queue,
+            position.field[0].len(),
+            position.field.len(),
+        );
+        #[cfg(not(feature = "alloc"))]
+        let mut tetris_manager = Self::with_mino_queue(tetris_config, mino_queue);
+        //空のフィールドでどのミノも出現できなければならない
+        if MINO_ARRAY.iter().any(|&mino_type| {
+            let (x, y) = tetris_manager.get_spawn_point(mino_type);
+            Mino::with_direction(
+                mino_type,
+                x,
+                y,
+                MinoDirection::North,
+                &tetris_manager.field,
+                &tetris_manager.tetris_config.rotation_system,
+            )
+            .is_none()
+        }) {
+            return Err(PositionError::SpawnOutOfField);
+        }
+        let (x, y) = position
+            .location
+            .unwrap_or_else(|| tetris_manager.get_spawn_point(position.current));
+        tetris_manager.current_mino = Mino::with_direction(
+            position.current,
+            x,
+            y,
+            position.direction,
+            &position.field,
+            &tetris_manager.tetris_config.rotation_system,
+        )
+        .ok_or(PositionError::PieceCollision)?;
+        tetris_manager.field = position.field;
+        tetris_manager.has_held = position.has_held;
+        tetris_manager.combo = position.combo;
+        tetris_manager.b2b = position.b2b;
+        tetris_manager.events.clear();
+        tetris_manager.emit(Event::Spawned {
+            mino_type: position.current,
+            x,
+            y,
+            direction: position.direction,
+        });
+        Ok(tetris_manager)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_BOARD_SIZE;
+
+    #[cfg(feature = "alloc")]
+    fn empty_field() -> Field {
+        alloc::vec![alloc::vec![Cell::Empty; DEFAULT_BOARD_SIZE.0]; DEFAULT_BOARD_SIZE.1]
+    }
+    #[cfg(not(feature = "alloc"))]
+    fn empty_field() -> Field {
+        crate::create_empty_field()
+    }
+    fn from_position(position: Position) -> Result<TetrisManager, PositionError> {
+        TetrisManager::from_position(TetrisConfig::default(), position, &0)
+    }
+
+    #[test]
+    fn accepts_valid_position() {
+        let mut field = empty_field();
+        field[DEFAULT_BOARD_SIZE.1 - 1][1..].fill(Cell::Obstruction(true));
+        let position = Position {
+            hold: Some(MinoType::MinoI),
+            has_held: true,
+            combo: 2,
+            b2b: 1,
+            ..Position::new(field.clone(), MinoType::MinoT)
+        };
+        let tetris_manager = from_position(position).unwrap();
+        assert!(tetris_manager.get_game_over().is_none());
+        assert_eq!(tetris_manager.get_field(), field);
+        assert_eq!(tetris_manager.current_mino.mino_type, MinoType::MinoT);
+    }
+
+    #[test]
+    fn rejects_invalid_field_size() {
+        let mut field = empty_field();
+        #[cfg(feature = "alloc")]
+        field[0].push(Cell::Empty);
+        #[cfg(not(feature = "alloc"))]
+        field.pop();
+        assert_eq!(
+            from_position(Position::new(field, MinoType::MinoT)).unwrap_err(),
+            PositionError::InvalidFieldSize
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_cell() {
+        let mut field = empty_field();
+        field[30][4] = Cell::Ghost(MinoType::MinoS);
+        assert_eq!(
+            from_position(Position::new(field, MinoType::MinoT)).unwrap_err(),
+            PositionError::InvalidCell { x: 4, y: 30 }
+        );
+    }
+
+    #[test]
+    fn rejects_full_row() {
+        let mut field = empty_field();
+        field[DEFAULT_BOARD_SIZE.1 - 2].fill(Cell::MinoBlock(MinoType::MinoL));
+        assert_eq!(
+            from_position(Position::new(field, MinoType::MinoT)).unwrap_err(),
+            PositionError::FullRow {
+                y: DEFAULT_BOARD_SIZE.1 - 2
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_held_without_hold() {
+        let position = Position {
+            has_held: true,
+            ..Position::new(empty_field(), MinoType::MinoT)
+        };
+        assert_eq!(
+            from_position(position).unwrap_err(),
+            PositionError::HeldWithoutHold
+        );
+    }
+
+    #[test]
+    fn rejects_piece_collision() {
+        let mut field = empty_field();
+        field[DEFAULT_BOARD_SIZE.1 - 1][4] = Cell::Obstruction(false);
+        let position = Position {
+            location: Some((3, DEFAULT_BOARD_SIZE.1 as i64 - 2)),
+            ..Position::new(field, MinoType::MinoT)
+        };
+        assert_eq!(
+            from_position(position).unwrap_err(),
+            PositionError::PieceCollision
+        );
+    }
+
+    #[test]
+    fn rejects_spawn_out_of_field() {
+        let tetris_config = TetrisConfig {
+            appearance_position: (DEFAULT_BOARD_SIZE.0 as i64, 19),
+            ..TetrisConfig::default()
+        };
+        let position = Position::new(empty_field(), MinoType::MinoT);
+        assert_eq!(
+            TetrisManager::from_position(tetris_config, position, &0).unwrap_err(),
+            PositionError::SpawnOutOfField
+        );
+        //出現位置に収まらない幅のフィールド
+        #[cfg(feature = "alloc")]
+        {
+            let field = alloc::vec![alloc::vec![Cell::Empty; 2]; DEFAULT_BOARD_SIZE.1];
+            let position = Position::new(field, MinoType::MinoT);
+            assert_eq!(
+                from_position(position).unwrap_err(),
+                PositionError::SpawnOutOfField
+            );
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[test]
+    fn rejects_queue_too_long() {
+        let mut position = Position::new(empty_field(), MinoType::MinoT);
+        while position.queue.push(MinoType::MinoI).is_ok() {}
+        assert_eq!(
+            from_position(position).unwrap_err(),
+            PositionError::Sequence(SequenceError::TooLong)
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::MINO_ARRAY;
+use crate::MinoType::{self, *};
+use enum_map::EnumMap;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha20Rng;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub trait Randomizer {
+    fn next_mino(&mut self, rng: &mut ChaCha20Rng) -> MinoType;
+    //bag方式でなければNone
+    fn bag_size(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BagState {
+    pub bag_size: usize,
+    pub position: usize, //現在のbagから既に取り出されたミノの数
+    pub remaining: EnumMap<MinoType, usize>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BagRandomizer {
+    pub(crate) copies: usize,
+    pub(crate) bag: heapless::Vec<MinoType, { MINO_ARRAY.len() * 2 }>,
+    pub(crate) position: usize,
+}
+impl BagRandomizer {
+    pub fn seven() -> Self {
+        Self::with_copies(1)
+    }
+    pub fn fourteen() -> Self {
+        Self::with_copies(2)
+    }
+    fn with_copies(copies: usize) -> Self {
+        Self {
+            copies,
+            bag: heapless::Vec::new(),
+            position: 0,
+        }
+    }
+    fn refill(&mut self, rng: &mut ChaCha20Rng) {
+        self.bag.clear();
+        for _ in 0..self.copies {
+            self.bag.extend_from_slice(&MINO_ARRAY).unwrap();
+        }
+        self.bag.shuffle(rng);
+        self.position = 0;
+    }
+}
+impl Randomizer for BagRandomizer {
+    fn next_mino(&mut self, rng: &mut ChaCha20Rng) -> MinoType {
+        if self.position >= self.bag.len() {
+            self.refill(rng);
+        }
+        self.position += 1;
+        self.bag[self.position - 1]
+    }
+    fn bag_size(&self) -> Option<usize> {
+        Some(MINO_ARRAY.len() * self.copies)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct PureRandomizer;
+impl Randomizer for PureRandomizer {
+    fn next_mino(&mut self, rng: &mut ChaCha20Rng) -> MinoType {
+        MINO_ARRAY[rng.random_range(0..MINO_ARRAY.len())]
+    }
+}
+
+// 直近4個の履歴に含まれるミノを引いたら指定回数まで引き直す(TGM方式)
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HistoryRandomizer {
+    pub(crate) history: [MinoType; 4],
+    pub(crate) rolls: usize,
+    pub(crate) is_first: bool,
+}
+impl HistoryRandomizer {
+    pub fn new(history: [MinoType; 4], rolls: usize) -> Self {
+        Self {
+            history,
+            rolls,
+            is_first: true,
+        }
+    }
+    pub fn tgm1() -> Self {
+        Self::new([MinoZ, MinoZ, MinoZ, MinoZ], 4)
+    }
+    pub fn tgm2() -> Self {
+        Self::new([MinoZ, MinoS, MinoS, MinoZ], 6)
+    }
+}
+impl Randomizer for HistoryRandomizer {
+    fn next_mino(&mut self, rng: &mut ChaCha20Rng) -> MinoType {
+        let mino = if self.is_first {
+            // 最初のミノはS,Z,Oにならない
+            self.is_first = false;
+            [MinoI, MinoJ, MinoL, MinoT][rng.random_range(0..4)]
+        } else {
+            let mut mino = MINO_ARRAY[rng.random_range(0..MINO_ARRAY.len())];
+            for _ in 1..self.rolls {
+                if !self.history.contains(&mino) {
+                    break;
+                }
+                mino = MINO_ARRAY[rng.random_range(0..MINO_ARRAY.len())];
+            }
+            mino
+        };
+        self.history.rotate_right(1);
+        self.history[0] = mino;
+        mino
+    }
+}
+
+// 8面ダイスを振り、8が出るか前回と同じなら1回だけ7面ダイスで引き直す(NES方式)
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct NesRandomizer {
+    pub(crate) previous: Option<MinoType>,
+}
+impl Randomizer for NesRandomizer {
+    fn next_mino(&mut self, rng: &mut ChaCha20Rng) -> MinoType {
+        let index = rng.random_range(0..MINO_ARRAY.len() + 1);
+        let mino = match MINO_ARRAY.get(index) {
+            Some(&mino) if Some(mino) != self.previous => mino,
+            _ => MINO_ARRAY[rng.random_range(0..MINO_ARRAY.len())],
+        };
+        self.previous = Some(mino);
+        mino
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MinoRandomizer {
+    Bag(BagRandomizer),
+    PureRandom(PureRandomizer),
+    History(HistoryRandomizer),
+    Nes(NesRandomizer),
+}
+impl Default for MinoRandomizer {
+    fn default() -> Self {
+        Self::Bag(BagRandomizer::seven())
+    }
+}
+impl Randomizer for MinoRandomizer {
+    fn next_mino(&mut self, rng: &mut ChaCha20Rng) -> MinoType {
+        match self {
+            Self::Bag(randomizer) => randomizer.next_mino(rng),
+            Self::PureRandom(randomizer) => randomizer.next_mino(rng),
+            Self::History(randomizer) => randomizer.next_mino(rng),
+            Self::Nes(randomizer) => randomizer.next_mino(rng),
+        }
+    }
+    fn bag_size(&self) -> Option<usize> {
+        match self {
+            Self::Bag(randomizer) => randomizer.bag_size(),
+            Self::PureRandom(randomizer) => randomizer.bag_size(),
+            Self::History(randomizer) => randomizer.bag_size(),
+            Self::Nes(randomizer) => randomizer.bag_size(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rng() -> ChaCha20Rng {
+        rand_seeder::Seeder::from(&0).into_rng()
+    }
+
+    #[test]
+    fn bag_contains_every_mino_per_bag() {
+        for (mut randomizer, copies) in
+            [(BagRandomizer::seven(), 1), (BagRandomizer::fourteen(), 2)]
+        {
+            let mut rng = rng();
+            for _ in 0..10 {
+                let mut counts = EnumMap::<MinoType, usize>::default();
+                for _ in 0..MINO_ARRAY.len() * copies {
+                    counts[randomizer.next_mino(&mut rng)] += 1;
+                }
+                assert!(counts.values().all(|&count| count == copies));
+            }
+        }
+    }
+
+    #[test]
+    fn history_rerolls_at_most_rolls_times() {
+        let mut rng = rng();
+        let mut randomizer = HistoryRandomizer::tgm1();
+        assert!([MinoI, MinoJ, MinoL, MinoT].contains(&randomizer.next_mino(&mut rng)));
+        for _ in 0..1000 {
+            let history = randomizer.history;
+            let mut expected_rng = rng.clone();
+            let mino = randomizer.next_mino(&mut rng);
+            //履歴にないミノが出るか引いた回数がrollsに達したら止まる
+            for roll in 1..=randomizer.rolls {
+                let drawn = MINO_ARRAY[expected_rng.random_range(0..MINO_ARRAY.len())];
+                if !history.contains(&drawn) || roll == randomizer.rolls {
+                    assert_eq!(drawn, mino);
+                    break;
+                }
+            }
+            assert_eq!(expected_rng, rng);
+            assert_eq!(randomizer.history[0], mino);
+        }
+    }
+
+    #[test]
+    fn history_without_roll_limit_avoids_history() {
+        let mut rng = rng();
+        let mut randomizer = HistoryRandomizer::new([MinoZ; 4], usize::MAX);
+        randomizer.next_mino(&mut rng);
+        for _ in 0..1000 {
+            let history = randomizer.history;
+            assert!(!history.contains(&randomizer.next_mino(&mut rng)));
+        }
+    }
+
+    #[test]
+    fn nes_rerolls_once_on_repeat() {
+        let mut rng = rng();
+        let mut randomizer = NesRandomizer::default();
+        for _ in 0..1000 {
+            let previous = randomizer.previous;
+            let mut expected_rng = rng.clone();
+            let mino = randomizer.next_mino(&mut rng);
+            //8が出るか前回と同じなら7面ダイスの結果をそのまま使う
+            let expected = match MINO_ARRAY.get(expected_rng.random_range(0..MINO_ARRAY.len() + 1))
+            {
+                Some(&drawn) if Some(drawn) != previous => drawn,
+                _ => MINO_ARRAY[expected_rng.random_range(0..MINO_ARRAY.len())],
+            };
+            assert_eq!(mino, expected);
+            assert_eq!(expected_rng, rng);
+            assert_eq!(randomizer.previous, Some(mino));
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use crate::{
+    Cell, CommandError, GameOver, LineClear, MinoRandomizer, MinoState, MinoType, MovementCommand,
+    Position, ReplayError, TetrisConfig, TetrisManager,
+};
+use alloc::vec::Vec;
+use enum_map::Enum;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayCommand {
+    pub movement_command: MovementCommand,
+    pub timestamp: Option<u64>, //単位(フレーム数やミリ秒)は記録する側が決める
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ReplayResult {
+    pub locked_minos: usize,
+    pub cleared_lines: usize,
+    pub max_combo: usize,
+    pub game_over: Option<GameOver>,
+    pub field_checksum: u64, //最終的なフィールドのチェックサム
+}
+impl ReplayResult {
+    fn record(&mut self, line_clear: &Option<LineClear>) {
+        if let Some(line_clear) = line_clear {
+            self.locked_minos += 1;
+            self.cleared_lines += line_clear.cleared_line_count;
+            self.max_combo = self.max_combo.max(line_clear.combo);
+        }
+    }
+    fn with_final_state(&self, tetris_manager: &TetrisManager) -> Self {
+        Self {
+            game_over: tetris_manager.get_game_over(),
+            field_checksum: field_checksum(&tetris_manager.get_field()),
+            ..*self
+        }
+    }
+}
+
+//記録開始時の状態(TetrisManagerのどのコンストラクタで開始したか)
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayStart {
+    Randomizer(MinoRandomizer),
+    Sequence {
+        sequence: Vec<MinoType>,
+        hold: Option<MinoType>,
+        fallback_randomizer: Option<MinoRandomizer>,
+    },
+    Position(Position), //fieldのサイズはReplayのwidthとheightに一致する
+}
+
+//seedはu64に固定する(Hashの実装が型ごとに異なるため)
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub tetris_config: TetrisConfig,
+    pub start: ReplayStart,
+    pub rng_seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub commands: Vec<ReplayCommand>,
+    pub result: Option<ReplayResult>, //記録時の最終状態
+}
+impl Replay {
+    pub fn new(tetris_config: TetrisConfig, rng_seed: u64, width: usize, height: usize) -> Self {
+        Self::with_randomizer(
+            tetris_config,
+            MinoRandomizer::default(),
+            rng_seed,
+            width,
+            height,
+        )
+    }
+    pub fn with_randomizer(
+        tetris_config: TetrisConfig,
+        randomizer: MinoRandomizer,
+        rng_seed: u64,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        Self::with_start(
+            tetris_config,
+            ReplayStart::Randomizer(randomizer),
+            rng_seed,
+            width,
+            height,
+        )
+    }
+    pub fn with_start(
+        tetris_config: TetrisConfig,
+        start: ReplayStart,
+        rng_seed: u64,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        Self {
+            tetris_config,
+            start,
+            rng_seed,
+            width,
+            height,
+            commands: Vec::new(),
+            result: None,
+        }
+    }
+    pub fn create_tetris_manager(&self) -> Result<TetrisManager, ReplayError> {
+        let tetris_config = self.tetris_config.clone();
+        match &self.start {
+            ReplayStart::Randomizer(randomizer) => Ok(TetrisManager::with_randomizer(
+                tetris_config,
+                randomizer.clone(),
+                &self.rng_seed,
+                self.width,
+                self.height,
+            )),
+            ReplayStart::Sequence {
+                sequence,
+                hold,
+                fallback_randomizer,
+            } => TetrisManager::with_sequence(
+                tetris_config,
+                sequence,
+                *hold,
+                fallback_randomizer.clone(),
+                &self.rng_seed,
+                self.width,
+                self.height,
+            )
+            .map_err(ReplayError::Sequence),
+            ReplayStart::Position(position) => {
+                if position.field.len() != self.height
+                    || position.field.iter().any(|row| row.len() != self.width)
+                {
+                    return Err(ReplayError::FieldSizeMismatch);
+                }
+                TetrisManager::from_position(tetris_config, position.clone(), &self.rng_seed)
+                    .map_err(ReplayError::Position)
+            }
+        }
+    }
+    pub fn play(&self) -> Result<ReplayResult, ReplayError> {
+        Ok(ReplayPlayer::new(self)?.play_to_end())
+    }
+    //再生した結果が記録時の最終状態と一致するか確かめる
+    pub fn verify(&self) -> Result<ReplayResult, ReplayError> {
+        let recorded = self.result.ok_or(ReplayError::MissingResult)?;
+        let replayed = self.play()?;
+        if recorded != replayed {
+            return Err(ReplayError::Mismatch { recorded, replayed });
+        }
+        Ok(replayed)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    tetris_manager: TetrisManager,
+    result: ReplayResult,
+}
+impl ReplayRecorder {
+    pub fn new(tetris_config: TetrisConfig, rng_seed: u64, width: usize, height: usize) -> Self {
+        Self::with_randomizer(
+            tetris_config,
+            MinoRandomizer::default(),
+            rng_seed,
+            width,
+            height,
+        )
+    }
+    pub fn with_randomizer(
+        tetris_config: TetrisConfig,
+        randomizer: MinoRandomizer,
+        rng_seed: u64,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        let tetris_manager = TetrisManager::with_randomizer(
+            tetris_config.clone(),
+            randomizer.clone(),
+            &rng_seed,
+            width,
+            height,
+        );
+        let replay = Replay::with_randomizer(tetris_config, randomizer, rng_seed, width, height);
+        Self {
+            replay,
+            tetris_manager,
+            result: ReplayResult::default(),
+        }
+    }
+    pub fn with_sequence(
+        tetris_config: TetrisConfig,
+        sequence: &[MinoType],
+        hold: Option<MinoType>,
+        fallback_randomizer: Option<MinoRandomizer>,
+        rng_seed: u64,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, ReplayError> {
+        let start = ReplayStart::Sequence {
+            sequence: sequence.to_vec(),
+            hold,
+            fallback_randomizer,
+        };
+        Self::from_replay(Replay::with_start(
+            tetris_config,
+            start,
+            rng_seed,
+            width,
+            height,
+        ))
+    }
+    pub fn from_position(
+        tetris_config: TetrisConfig,
+        position: Position,
+        rng_seed: u64,
+    ) -> Result<Self, ReplayError> {
+        let width = position.field.first().map_or(0, |row| row.len());
+        let height = position.field.len();
+        Self::from_replay(Replay::with_start(
+            tetris_config,
+            ReplayStart::Position(position),
+            rng_seed,
+            width,
+            height,
+        ))
+    }
+    fn from_replay(replay: Replay) -> Result<Self, ReplayError> {
+        Ok(Self {
+            tetris_manager: replay.create_tetris_manager()?,
+            replay,
+            result: ReplayResult::default(),
+        })
+    }
+    pub fn command(
+        &mut self,
+        movement_command: MovementCommand,
+        timestamp: Option<u64>,
+    ) -> (Result<MinoState, CommandError>, Option<LineClear>, bool) {
+        let r = self.tetris_manager.command(movement_command.clone());
+        self.result.record(&r.1);
+        self.replay.commands.push(ReplayCommand {
+            movement_command,
+            timestamp,
+        });
+        r
+    }
+    pub fn get_tetris_manager(&self) -> &TetrisManager {
+        &self.tetris_manager
+    }
+    pub fn get_result(&self) -> ReplayResult {
+        self.result.with_final_state(&self.tetris_manager)
+    }
+    pub fn finish(mut self) -> Replay {
+        self.replay.result = Some(self.get_result());
+        self.replay
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayPlayer<'a> {
+    replay: &'a Replay,
+    tetris_manager: TetrisManager,
+    position: usize, //次に実行するコマンドの位置
+    result: ReplayResult,
+}
+impl<'a> ReplayPlayer<'a> {
+    pub fn new(replay: &'a Replay) -> Result<Self, ReplayError> {
+        Ok(Self {
+            replay,
+            tetris_manager: replay.create_tetris_manager()?,
+            position: 0,
+            result: ReplayResult::default(),
+        })
+    }
+    //コマンドを1つ実行する(最後まで実行済みならNone)
+    pub fn step(&mut self) -> Option<(Result<MinoState, CommandError>, Option<LineClear>, bool)> {
+        let replay_command = self.replay.commands.get(self.position)?;
+        self.position += 1;
+        let r = self
+            .tetris_manager
+            .command(replay_command.movement_command.clone());
+        self.result.record(&r.1);
+        Some(r)
+    }
+    //timestamp以前に記録されたコマンドを全て実行する
+    pub fn play_until(&mut self, timestamp: u64) {
+        while self
+            .get_next_command()
+            .is_some_and(|command| command.timestamp.is_none_or(|t| t <= timestamp))
+        {
+            self.step();
+        }
+    }
+    pub fn play_to_end(mut self) -> ReplayResult {
+        while self.step().is_some() {}
+        self.get_result()
+    }
+    pub fn get_next_command(&self) -> Option<&'a ReplayCommand> {
+        self.replay.commands.get(self.position)
+    }
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.replay.commands.len()
+    }
+    pub fn get_tetris_manager(&self) -> &TetrisManager {
+        &self.tetris_manager
+    }
+    pub fn get_result(&self) -> ReplayResult {
+        self.result.with_final_state(&self.tetris_manager)
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+//プラットフォームに依存しないようにFNV-1aで計算する
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+pub(crate) fn cell_code(cell: &Cell) -> u8 {
+    match cell {
+        Cell::Empty => 0,
+        Cell::Wall => 1,
+        Cell::Obstruction(false) => 2,
+        Cell::Obstruction(true) => 3,
+        Cell::MinoBlock(mino_type) => 4 + mino_type.into_usize() as u8,
+        Cell::MinoInMotion(mino_type) => 11 + mino_type.into_usize() as u8,
+        Cell::Ghost(mino_type) => 18 + mino_type.into_usize() as u8,
+    }
+}
+pub fn field_checksum(field: &[Vec<Cell>]) -> u64 {
+    fnv1a(field.iter().flat_map(|row| row.iter().map(cell_code)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttackedLine, GarbageDelay};
+
+    fn record() -> Replay {
+        let tetris_config = TetrisConfig {
+            garbage_delay: GarbageDelay::Frames(2),
+            ..TetrisConfig::default()
+        };
+        let mut recorder = ReplayRecorder::with_sequence(
+            tetris_config,
+            &[MinoType::MinoI, MinoType::MinoO, MinoType::MinoT],
+            None,
+            Some(MinoRandomizer::default()),
+            11,
+            10,
+            42,
+        )
+        .unwrap();
+        let attacked_line = AttackedLine {
+            hole_indexes: Some(alloc::vec![0]),
+            can_be_cleared: true,
+        };
+        let commands = [
+            MovementCommand::Attacked(attacked_line.clone()),
+            MovementCommand::Attacked(attacked_line),
+            MovementCommand::CancelGarbage(1),
+            MovementCommand::ElapseGarbageFrames(2),
+            MovementCommand::Left,
+            MovementCommand::HardDrop,
+            MovementCommand::RotateClockWise,
+            MovementCommand::Right,
+            MovementCommand::HardDrop,
+            MovementCommand::Hold,
+            MovementCommand::HardDrop,
+        ];
+        for (timestamp, movement_command) in commands.into_iter().enumerate() {
+            let _ = recorder.command(movement_command, Some(timestamp as u64));
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn recorded_replay_verifies() {
+        let replay = record();
+        let recorded = replay.result.unwrap();
+        assert_eq!(recorded.locked_minos, 3);
+        assert_eq!(replay.verify(), Ok(recorded));
+
+        //途中まで再生したときの状態も記録時と同じ順に進む
+        let mut player = ReplayPlayer::new(&replay).unwrap();
+        player.play_until(3);
+        assert_eq!(player.get_tetris_manager().get_pending_garbage_lines(), 1);
+        assert_eq!(
+            player
+                .get_tetris_manager()
+                .get_garbage_queue()
+                .get_ready_lines(),
+            1
+        );
+        assert!(!player.is_finished());
+        assert_eq!(player.play_to_end(), recorded);
+    }
+
+    #[test]
+    fn tampered_replay_fails_to_verify() {
+        let replay = record();
+        //相殺を記録から消すとせり上がるライン数が変わる
+        let mut tampered = replay.clone();
+        tampered.commands.retain(|command| {
+            !matches!(command.movement_command, MovementCommand::CancelGarbage(_))
+        });
+        assert!(matches!(
+            tampered.verify(),
+            Err(ReplayError::Mismatch { .. })
+        ));
+
+        let mut tampered = replay.clone();
+        tampered.commands[4].movement_command = MovementCommand::Right;
+        assert!(matches!(
+            tampered.verify(),
+            Err(ReplayError::Mismatch { .. })
+        ));
+
+        //開始時のミノ列が違えば結果も変わる
+        let tampered = Replay {
+            start: ReplayStart::Randomizer(MinoRandomizer::default()),
+            ..replay.clone()
+        };
+        assert!(matches!(
+            tampered.verify(),
+            Err(ReplayError::Mismatch { .. })
+        ));
+
+        let without_result = Replay {
+            result: None,
+            ..replay
+        };
+        assert_eq!(without_result.verify(), Err(ReplayError::MissingResult));
+    }
+
+    #[test]
+    fn invalid_start_is_reported() {
+        let replay = Replay::with_start(
+            TetrisConfig::default(),
+            ReplayStart::Sequence {
+                sequence: Vec::new(),
+                hold: None,
+                fallback_randomizer: None,
+            },
+            0,
+            10,
+            42,
+        );
+        assert_eq!(
+            replay.play(),
+            Err(ReplayError::Sequence(crate::SequenceError::Empty))
+        );
+        let position = Position::new(
+            alloc::vec![alloc::vec![Cell::Empty; 10]; 42],
+            MinoType::MinoT,
+        );
+        let replay = Replay::with_start(
+            TetrisConfig::default(),
+            ReplayStart::Position(position),
+            0,
+            10,
+            40,
+        );
+        assert_eq!(replay.play(), Err(ReplayError::FieldSizeMismatch));
+    }
+}
@@ -0,0 +1,993 @@
+use crate::replay::{cell_code, fnv1a};
+use crate::{
+    AttackedLine, BagRandomizer, Cell, GameOver, GarbageDelay, HistoryRandomizer, LockOutRule,
+    MINO_ARRAY, MinoRandomizer, MinoType, MovementCommand, NesRandomizer, Position, PureRandomizer,
+    Replay, ReplayCommand, ReplayDecodeError, ReplayResult, ReplayStart, Rotate180Kicks,
+    RotationSystem, RotationTable, RotationType, SpinRule, TetrisConfig,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+use enum_map::{Enum, EnumMap};
+
+//フォーマットを変更したら上げる
+pub const REPLAY_FORMAT_VERSION: u8 = 1;
+const MAGIC: &[u8; 4] = b"TCRP";
+//記録時とcrateのバージョンが異なると再生結果が変わる可能性がある
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+const CHECKSUM_SIZE: usize = 8;
+const OPCODE_BITS: u32 = 4;
+//信頼できないデータで巨大な確保やオーバーフローが起きないようにする上限
+const MAX_FIELD_SIZE: usize = 1024;
+const MAX_PREVIEW_COUNT: usize = 1024;
+const MAX_OFFSET: i64 = MAX_FIELD_SIZE as i64; //出現位置と壁蹴りのオフセットの絶対値
+
+//全体は先頭から詰めたビット列で、最後にそれまでのバイト列のFNV-1a(8バイト,LE)が続く
+//magic(4バイト) format_version(8bit) crate_version seed width height config start
+//コマンド数 [opcode(4bit) timestampの有無(1bit) (timestampの差分) (Attackedの内容)]...
+//result
+impl Replay {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        for &byte in MAGIC {
+            writer.write_u8(byte);
+        }
+        writer.write_u8(REPLAY_FORMAT_VERSION);
+        writer.write_str(CRATE_VERSION);
+        writer.write_varint(self.rng_seed);
+        writer.write_usize(self.width);
+        writer.write_usize(self.height);
+        writer.write_config(&self.tetris_config);
+        writer.write_start(&self.start);
+        writer.write_usize(self.commands.len());
+        let mut previous_timestamp = 0;
+        for replay_command in self.commands.iter() {
+            writer.write_command(&replay_command.movement_command);
+            writer.write_bool(replay_command.timestamp.is_some());
+            if let Some(timestamp) = replay_command.timestamp {
+                //timestampは直前のtimestampとの差分で記録する
+                writer.write_zigzag(timestamp.wrapping_sub(previous_timestamp) as i64);
+                previous_timestamp = timestamp;
+            }
+        }
+        writer.write_bool(self.result.is_some());
+        if let Some(result) = &self.result {
+            writer.write_result(result);
+        }
+        let mut bytes = writer.bytes;
+        let checksum = fnv1a(bytes.iter().copied());
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayDecodeError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(ReplayDecodeError::InvalidMagic);
+        }
+        let Some(body_len) = bytes.len().checked_sub(CHECKSUM_SIZE) else {
+            return Err(ReplayDecodeError::UnexpectedEnd);
+        };
+        let (body, checksum) = bytes.split_at(body_len);
+        if fnv1a(body.iter().copied()).to_le_bytes() != checksum {
+            return Err(ReplayDecodeError::ChecksumMismatch);
+        }
+        let mut reader = BitReader::new(&body[MAGIC.len()..]);
+        let format_version = reader.read_u8()?;
+        if format_version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayDecodeError::UnsupportedFormatVersion(format_version));
+        }
+        let crate_version = reader.read_string()?;
+        if crate_version != CRATE_VERSION {
+            return Err(ReplayDecodeError::CrateVersionMismatch(crate_version));
+        }
+        let rng_seed = reader.read_varint()?;
+        let width = reader.read_bounded(1..=MAX_FIELD_SIZE)?;
+        let height = reader.read_bounded(1..=MAX_FIELD_SIZE)?;
+        let tetris_config = reader.read_config()?;
+        let start = reader.read_start(width, height)?;
+        let command_count = reader.read_usize()?;
+        let mut commands = Vec::new();
+        let mut previous_timestamp: u64 = 0;
+        for _ in 0..command_count {
+            let movement_command = reader.read_command()?;
+            let timestamp = if reader.read_bool()? {
+                previous_timestamp = previous_timestamp.wrapping_add(reader.read_zigzag()? as u64);
+                Some(previous_timestamp)
+            } else {
+                None
+            };
+            commands.push(ReplayCommand {
+                movement_command,
+                timestamp,
+            });
+        }
+        let result = if reader.read_bool()? {
+            Some(reader.read_result()?)
+        } else {
+            None
+        };
+        //残りは最後のバイトを埋めるための0だけのはず
+        let padding = reader.remaining_bits();
+        if padding >= 8 || reader.read_bits(padding as u32)? != 0 {
+            return Err(ReplayDecodeError::InvalidValue);
+        }
+        Ok(Self {
+            tetris_config,
+            start,
+            rng_seed,
+            width,
+            height,
+            commands,
+            result,
+        })
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_len: usize,
+}
+impl BitWriter {
+    //上位ビットから順に書き込む
+    fn write_bits(&mut self, value: u64, count: u32) {
+        for i in (0..count).rev() {
+            if self.bit_len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.bit_len % 8);
+            }
+            self.bit_len += 1;
+        }
+    }
+    fn write_bool(&mut self, value: bool) {
+        self.write_bits(value as u64, 1);
+    }
+    fn write_u8(&mut self, value: u8) {
+        self.write_bits(value as u64, 8);
+    }
+    //7bitずつ下位から書き込み、続きがあれば先頭のbitを1にする(LEB128)
+    fn write_varint(&mut self, mut value: u64) {
+        loop {
+            let group = value & 0x7f;
+            value >>= 7;
+            if value == 0 {
+                self.write_u8(group as u8);
+                return;
+            }
+            self.write_u8(group as u8 | 0x80);
+        }
+    }
+    fn write_usize(&mut self, value: usize) {
+        self.write_varint(value as u64);
+    }
+    fn write_zigzag(&mut self, value: i64) {
+        self.write_varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+    fn write_option_usize(&mut self, value: Option<usize>) {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            self.write_usize(value);
+        }
+    }
+    fn write_str(&mut self, value: &str) {
+        self.write_usize(value.len());
+        for &byte in value.as_bytes() {
+            self.write_u8(byte);
+        }
+    }
+    fn write_enum<T: Enum>(&mut self, value: T, count: u32) {
+        self.write_bits(value.into_usize() as u64, count);
+    }
+    fn write_config(&mut self, tetris_config: &TetrisConfig) {
+        self.write_option_usize(tetris_config.move_reset_limit);
+        self.write_zigzag(tetris_config.appearance_position.0);
+        self.write_zigzag(tetris_config.appearance_position.1);
+        self.write_bool(tetris_config.can_hold_infinity);
+        self.write_u8(match tetris_config.spin_rule {
+            SpinRule::TOnly => 0,
+            SpinRule::Guideline => 1,
+            SpinRule::ImmobileAllSpin => 2,
+            SpinRule::AllMini => 3,
+            SpinRule::Tetrio => 4,
+        });
+        self.write_usize(tetris_config.preview_count);
+        self.write_rotation_system(&tetris_config.rotation_system);
+        self.write_u8(match tetris_config.rotate180_kicks {
+            Rotate180Kicks::Disabled => 0,
+            Rotate180Kicks::NoKick => 1,
+            Rotate180Kicks::RotationSystem => 2,
+            Rotate180Kicks::Nullpomino => 3,
+        });
+        match tetris_config.garbage_delay {
+            GarbageDelay::Pieces(pieces) => {
+                self.write_u8(0);
+                self.write_usize(pieces);
+            }
+            GarbageDelay::Frames(frames) => {
+                self.write_u8(1);
+                self.write_usize(frames);
+            }
+        }
+        self.write_option_usize(tetris_config.garbage_cap);
+        self.write_bool(tetris_config.is_garbage_blocked_by_line_clear);
+        self.write_usize(tetris_config.visible_height);
+        self.write_u8(match tetris_config.lock_out_rule {
+            LockOutRule::Disabled => 0,
+            LockOutRule::LockOut => 1,
+            LockOutRule::PartialLockOut => 2,
+        });
+    }
+    //途中の状態も含めて書き込む
+    fn write_randomizer(&mut self, randomizer: &MinoRandomizer) {
+        match randomizer {
+            MinoRandomizer::Bag(bag_randomizer) => {
+                self.write_u8(0);
+                self.write_usize(bag_randomizer.copies);
+                self.write_usize(bag_randomizer.bag.len());
+                for &mino_type in bag_randomizer.bag.iter() {
+                    self.write_enum(mino_type, 3);
+                }
+                self.write_usize(bag_randomizer.position);
+            }
+            MinoRandomizer::PureRandom(_) => self.write_u8(1),
+            MinoRandomizer::History(history_randomizer) => {
+                self.write_u8(2);
+                for &mino_type in history_randomizer.history.iter() {
+                    self.write_enum(mino_type, 3);
+                }
+                self.write_usize(history_randomizer.rolls);
+                self.write_bool(history_randomizer.is_first);
+            }
+            MinoRandomizer::Nes(nes_randomizer) => {
+                self.write_u8(3);
+                self.write_bool(nes_randomizer.previous.is_some());
+                if let Some(previous) = nes_randomizer.previous {
+                    self.write_enum(previous, 3);
+                }
+            }
+        }
+    }
+    fn write_option_randomizer(&mut self, randomizer: &Option<MinoRandomizer>) {
+        self.write_bool(randomizer.is_some());
+        if let Some(randomizer) = randomizer {
+            self.write_randomizer(randomizer);
+        }
+    }
+    fn write_option_mino(&mut self, mino_type: Option<MinoType>) {
+        self.write_bool(mino_type.is_some());
+        if let Some(mino_type) = mino_type {
+            self.write_enum(mino_type, 3);
+        }
+    }
+    fn write_minos(&mut self, minos: &[MinoType]) {
+        self.write_usize(minos.len());
+        for &mino_type in minos {
+            self.write_enum(mino_type, 3);
+        }
+    }
+    //フィールドのサイズはヘッダのwidthとheightと同じなので記録しない
+    fn write_start(&mut self, start: &ReplayStart) {
+        match start {
+            ReplayStart::Randomizer(randomizer) => {
+                self.write_u8(0);
+                self.write_randomizer(randomizer);
+            }
+            ReplayStart::Sequence {
+                sequence,
+                hold,
+                fallback_randomizer,
+            } => {
+                self.write_u8(1);
+                self.write_minos(sequence);
+                self.write_option_mino(*hold);
+                self.write_option_randomizer(fallback_randomizer);
+            }
+            ReplayStart::Position(position) => {
+                self.write_u8(2);
+                for cell in position.field.iter().flatten() {
+                    self.write_u8(cell_code(cell));
+                }
+                self.write_enum(position.current, 3);
+                self.write_enum(position.direction, 2);
+                self.write_bool(position.location.is_some());
+                if let Some((x, y)) = position.location {
+                    self.write_zigzag(x);
+                    self.write_zigzag(y);
+                }
+                self.write_option_mino(position.hold);
+                self.write_bool(position.has_held);
+                self.write_minos(&position.queue);
+                self.write_option_randomizer(&position.randomizer);
+                self.write_usize(position.combo);
+                self.write_usize(position.b2b);
+            }
+        }
+    }
+    fn write_rotation_system(&mut self, rotation_system: &RotationSystem) {
+        match rotation_system {
+            RotationSystem::Srs => self.write_u8(0),
+            RotationSystem::SrsPlus => self.write_u8(1),
+            RotationSystem::Ars => self.write_u8(2),
+            RotationSystem::Nrs => self.write_u8(3),
+            RotationSystem::NoKick => self.write_u8(4),
+            RotationSystem::Custom(rotation_table) => {
+                self.write_u8(5);
+                for shape in rotation_table
+                    .rotations
+                    .values()
+                    .flat_map(|shapes| shapes.values())
+                {
+                    for &block in shape.iter().flatten() {
+                        self.write_zigzag(block);
+                    }
+                }
+                for offsets in rotation_table
+                    .offsets
+                    .values()
+                    .flat_map(|offsets| offsets.values())
+                    .flat_map(|offsets| offsets.values())
+                {
+                    self.write_usize(offsets.len());
+                    for &(x, y) in offsets.iter() {
+                        self.write_zigzag(x);
+                        self.write_zigzag(y);
+                    }
+                }
+                for &is_center_column in rotation_table.center_column_rule.values() {
+                    self.write_bool(is_center_column);
+                }
+            }
+        }
+    }
+    fn write_command(&mut self, movement_command: &MovementCommand) {
+        let opcode = match movement_command {
+            MovementCommand::Left => 0,
+            MovementCommand::Right => 1,
+            MovementCommand::Down => 2,
+            MovementCommand::RotateClockWise => 3,
+            MovementCommand::Rotate180 => 4,
+            MovementCommand::RotateCounterClockWise => 5,
+            MovementCommand::Hold => 6,
+            MovementCommand::Lock => 7,
+            MovementCommand::HardDrop => 8,
+            MovementCommand::Attacked(_) => 9,
+            MovementCommand::InitialRotate(RotationType::Clockwise) => 10,
+            MovementCommand::InitialRotate(RotationType::Rotate180) => 11,
+            MovementCommand::InitialRotate(RotationType::CounterClockwise) => 12,
+            MovementCommand::InitialHold => 13,
+            MovementCommand::CancelGarbage(_) => 14,
+            MovementCommand::ElapseGarbageFrames(_) => 15,
+        };
+        self.write_bits(opcode, OPCODE_BITS);
+        if let MovementCommand::Attacked(attacked_line) = movement_command {
+            self.write_bool(attacked_line.can_be_cleared);
+            self.write_bool(attacked_line.hole_indexes.is_some());
+            if let Some(hole_indexes) = &attacked_line.hole_indexes {
+                self.write_usize(hole_indexes.len());
+                for &hole_index in hole_indexes.iter() {
+                    self.write_usize(hole_index);
+                }
+            }
+        }
+        if let MovementCommand::CancelGarbage(amount)
+        | MovementCommand::ElapseGarbageFrames(amount) = movement_command
+        {
+            self.write_usize(*amount);
+        }
+    }
+    fn write_result(&mut self, result: &ReplayResult) {
+        self.write_usize(result.locked_minos);
+        self.write_usize(result.cleared_lines);
+        self.write_usize(result.max_combo);
+        self.write_u8(match result.game_over {
+            None => 0,
+            Some(GameOver::BlockOut) => 1,
+            Some(GameOver::LockOut) => 2,
+            Some(GameOver::TopOut) => 3,
+            Some(GameOver::OutOfMinos) => 4,
+        });
+        self.write_bits(result.field_checksum, 64);
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize, //読み込んだbit数
+}
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+    fn remaining_bits(&self) -> usize {
+        self.bytes.len() * 8 - self.position
+    }
+    fn read_bits(&mut self, count: u32) -> Result<u64, ReplayDecodeError> {
+        if self.remaining_bits() < count as usize {
+            return Err(ReplayDecodeError::UnexpectedEnd);
+        }
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = (self.bytes[self.position / 8] >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+    fn read_bool(&mut self) -> Result<bool, ReplayDecodeError> {
+        Ok(self.read_bits(1)? == 1)
+    }
+    fn read_u8(&mut self) -> Result<u8, ReplayDecodeError> {
+        Ok(self.read_bits(8)? as u8)
+    }
+    fn read_varint(&mut self) -> Result<u64, ReplayDecodeError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let group = self.read_u8()?;
+            let bits = (group & 0x7f) as u64;
+            if bits << shift >> shift != bits {
+                return Err(ReplayDecodeError::InvalidValue);
+            }
+            value |= bits << shift;
+            if group & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayDecodeError::InvalidValue)
+    }
+    fn read_usize(&mut self) -> Result<usize, ReplayDecodeError> {
+        usize::try_from(self.read_varint()?).map_err(|_| ReplayDecodeError::InvalidValue)
+    }
+    fn read_bounded(
+        &mut self,
+        range: core::ops::RangeInclusive<usize>,
+    ) -> Result<usize, ReplayDecodeError> {
+        let value = self.read_usize()?;
+        if !range.contains(&value) {
+            return Err(ReplayDecodeError::InvalidValue);
+        }
+        Ok(value)
+    }
+    fn read_zigzag(&mut self) -> Result<i64, ReplayDecodeError> {
+        let value = self.read_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+    fn read_offset(&mut self) -> Result<i64, ReplayDecodeError> {
+        let value = self.read_zigzag()?;
+        if !(-MAX_OFFSET..=MAX_OFFSET).contains(&value) {
+            return Err(ReplayDecodeError::InvalidValue);
+        }
+        Ok(value)
+    }
+    fn read_option_usize(&mut self) -> Result<Option<usize>, ReplayDecodeError> {
+        if self.read_bool()? {
+            Ok(Some(self.read_usize()?))
+        } else {
+            Ok(None)
+        }
+    }
+    fn read_string(&mut self) -> Result<String, ReplayDecodeError> {
+        let len = self.read_usize()?;
+        let mut bytes = Vec::new();
+        for _ in 0..len {
+            bytes.push(self.read_u8()?);
+        }
+        String::from_utf8(bytes).map_err(|_| ReplayDecodeError::InvalidValue)
+    }
+    fn read_enum<T: Enum>(&mut self, count: u32) -> Result<T, ReplayDecodeError> {
+        let value = self.read_bits(count)? as usize;
+        if value >= T::LENGTH {
+            return Err(ReplayDecodeError::InvalidValue);
+        }
+        Ok(T::from_usize(value))
+    }
+    fn read_config(&mut self) -> Result<TetrisConfig, ReplayDecodeError> {
+        Ok(TetrisConfig {
+            move_reset_limit: self.read_option_usize()?,
+            appearance_position: (self.read_offset()?, self.read_offset()?),
+            can_hold_infinity: self.read_bool()?,
+            spin_rule: match self.read_u8()? {
+                0 => SpinRule::TOnly,
+                1 => SpinRule::Guideline,
+                2 => SpinRule::ImmobileAllSpin,
+                3 => SpinRule::AllMini,
+                4 => SpinRule::Tetrio,
+                _ => return Err(ReplayDecodeError::InvalidValue),
+            },
+            preview_count: self.read_bounded(0..=MAX_PREVIEW_COUNT)?,
+            rotation_system: self.read_rotation_system()?,
+            rotate180_kicks: match self.read_u8()? {
+                0 => Rotate180Kicks::Disabled,
+                1 => Rotate180Kicks::NoKick,
+                2 => Rotate180Kicks::RotationSystem,
+                3 => Rotate180Kicks::Nullpomino,
+                _ => return Err(ReplayDecodeError::InvalidValue),
+            },
+            garbage_delay: match self.read_u8()? {
+                0 => GarbageDelay::Pieces(self.read_usize()?),
+                1 => GarbageDelay::Frames(self.read_usize()?),
+                _ => return Err(ReplayDecodeError::InvalidValue),
+            },
+            garbage_cap: self.read_option_usize()?,
+            is_garbage_blocked_by_line_clear: self.read_bool()?,
+            visible_height: self.read_usize()?,
+            lock_out_rule: match self.read_u8()? {
+                0 => LockOutRule::Disabled,
+                1 => LockOutRule::LockOut,
+                2 => LockOutRule::PartialLockOut,
+                _ => return Err(ReplayDecodeError::InvalidValue),
+            },
+        })
+    }
+    fn read_option_randomizer(&mut self) -> Result<Option<MinoRandomizer>, ReplayDecodeError> {
+        Ok(if self.read_bool()? {
+            Some(self.read_randomizer()?)
+        } else {
+            None
+        })
+    }
+    fn read_option_mino(&mut self) -> Result<Option<MinoType>, ReplayDecodeError> {
+        Ok(if self.read_bool()? {
+            Some(self.read_enum(3)?)
+        } else {
+            None
+        })
+    }
+    fn read_minos(&mut self) -> Result<Vec<MinoType>, ReplayDecodeError> {
+        let len = self.read_usize()?;
+        //1ミノあたり3bitなので残りのビット数より長い列は存在しない
+        if len > self.remaining_bits() / 3 {
+            return Err(ReplayDecodeError::UnexpectedEnd);
+        }
+        (0..len).map(|_| self.read_enum(3)).collect()
+    }
+    //操作中のミノとゴーストは局面に含まれない
+    fn read_cell(&mut self) -> Result<Cell, ReplayDecodeError> {
+        Ok(match self.read_u8()? {
+            0 => Cell::Empty,
+            1 => Cell::Wall,
+            2 => Cell::Obstruction(false),
+            3 => Cell::Obstruction(true),
+            code @ 4..=10 => Cell::MinoBlock(MinoType::from_usize(code as usize - 4)),
+            _ => return Err(ReplayDecodeError::InvalidValue),
+        })
+    }
+    fn read_start(
+        &mut self,
+        width: usize,
+        height: usize,
+    ) -> Result<ReplayStart, ReplayDecodeError> {
+        Ok(match self.read_u8()? {
+            0 => ReplayStart::Randomizer(self.read_randomizer()?),
+            1 => ReplayStart::Sequence {
+                sequence: self.read_minos()?,
+                hold: self.read_option_mino()?,
+                fallback_randomizer: self.read_option_randomizer()?,
+            },
+            2 => {
+                if width * height > self.remaining_bits() / 8 {
+                    return Err(ReplayDecodeError::UnexpectedEnd);
+                }
+                let mut field = Vec::with_capacity(height);
+                for _ in 0..height {
+                    let row = (0..width)
+                        .map(|_| self.read_cell())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    field.push(row);
+                }
+                let current = self.read_enum(3)?;
+                let direction = self.read_enum(2)?;
+                let location = if self.read_bool()? {
+                    Some((self.read_offset()?, self.read_offset()?))
+                } else {
+                    None
+                };
+                ReplayStart::Position(Position {
+                    field,
+                    current,
+                    direction,
+                    location,
+                    hold: self.read_option_mino()?,
+                    has_held: self.read_bool()?,
+                    queue: self.read_minos()?,
+                    randomizer: self.read_option_randomizer()?,
+                    combo: self.read_usize()?,
+                    b2b: self.read_usize()?,
+                })
+            }
+            _ => return Err(ReplayDecodeError::InvalidValue),
+        })
+    }
+    fn read_randomizer(&mut self) -> Result<MinoRandomizer, ReplayDecodeError> {
+        Ok(match self.read_u8()? {
+            0 => {
+                let copies = self.read_bounded(1..=2)?;
+                let mut bag = heapless::Vec::new();
+                for _ in 0..self.read_usize()? {
+                    bag.push(self.read_enum(3)?)
+                        .map_err(|_| ReplayDecodeError::InvalidValue)?;
+                }
+                //bagは空(最初のミノの生成前)か中身が全て入っている
+                if !bag.is_empty() && bag.len() != MINO_ARRAY.len() * copies {
+                    return Err(ReplayDecodeError::InvalidValue);
+                }
+                MinoRandomizer::Bag(BagRandomizer {
+                    copies,
+                    position: self.read_bounded(0..=bag.len())?,
+                    bag,
+                })
+            }
+            1 => MinoRandomizer::PureRandom(PureRandomizer),
+            2 => {
+                let mut history = [MinoType::MinoI; 4];
+                for mino_type in history.iter_mut() {
+                    *mino_type = self.read_enum(3)?;
+                }
+                MinoRandomizer::History(HistoryRandomizer {
+                    history,
+                    rolls: self.read_usize()?,
+                    is_first: self.read_bool()?,
+                })
+            }
+            3 => MinoRandomizer::Nes(NesRandomizer {
+                previous: if self.read_bool()? {
+                    Some(self.read_enum(3)?)
+                } else {
+                    None
+                },
+            }),
+            _ => return Err(ReplayDecodeError::InvalidValue),
+        })
+    }
+    fn read_rotation_system(&mut self) -> Result<RotationSystem, ReplayDecodeError> {
+        Ok(match self.read_u8()? {
+            0 => RotationSystem::Srs,
+            1 => RotationSystem::SrsPlus,
+            2 => RotationSystem::Ars,
+            3 => RotationSystem::Nrs,
+            4 => RotationSystem::NoKick,
+            5 => {
+                let mut rotation_table = RotationTable {
+                    rotations: EnumMap::default(),
+                    offsets: EnumMap::default(),
+                    center_column_rule: EnumMap::default(),
+                };
+                for shape in rotation_table
+                    .rotations
+                    .values_mut()
+                    .flat_map(|shapes| shapes.values_mut())
+                {
+                    for block in shape.iter_mut().flatten() {
+                        *block = self.read_zigzag()?;
+                    }
+                    //ブロックのないミノはどこまでも落下できてしまう
+                    if shape
+                        .iter()
+                        .flatten()
+                        .any(|&block| block != 0 && block != 1)
+                        || !shape.iter().flatten().any(|&block| block == 1)
+                    {
+                        return Err(ReplayDecodeError::InvalidValue);
+                    }
+                }
+                for offsets in rotation_table
+                    .offsets
+                    .values_mut()
+                    .flat_map(|offsets| offsets.values_mut())
+                    .flat_map(|offsets| offsets.values_mut())
+                {
+                    for _ in 0..self.read_usize()? {
+                        offsets.push((self.read_offset()?, self.read_offset()?));
+                    }
+                }
+                for is_center_column in rotation_table.center_column_rule.values_mut() {
+                    *is_center_column = self.read_bool()?;
+                }
+                RotationSystem::Custom(rotation_table)
+            }
+            _ => return Err(ReplayDecodeError::InvalidValue),
+        })
+    }
+    fn read_command(&mut self) -> Result<MovementCommand, ReplayDecodeError> {
+        Ok(match self.read_bits(OPCODE_BITS)? {
+            0 => MovementCommand::Left,
+            1 => MovementCommand::Right,
+            2 => MovementCommand::Down,
+            3 => MovementCommand::RotateClockWise,
+            4 => MovementCommand::Rotate180,
+            5 => MovementCommand::RotateCounterClockWise,
+            6 => MovementCommand::Hold,
+            7 => MovementCommand::Lock,
+            8 => MovementCommand::HardDrop,
+            9 => {
+                let can_be_cleared = self.read_bool()?;
+                let hole_indexes = if self.read_bool()? {
+                    let mut hole_indexes = Vec::new();
+                    for _ in 0..self.read_usize()? {
+                        hole_indexes.push(self.read_usize()?);
+                    }
+                    Some(hole_indexes)
+                } else {
+                    None
+                };
+                MovementCommand::Attacked(AttackedLine {
+                    hole_indexes,
+                    can_be_cleared,
+                })
+            }
+            10 => MovementCommand::InitialRotate(RotationType::Clockwise),
+            11 => MovementCommand::InitialRotate(RotationType::Rotate180),
+            12 => MovementCommand::InitialRotate(RotationType::CounterClockwise),
+            13 => MovementCommand::InitialHold,
+            14 => MovementCommand::CancelGarbage(self.read_usize()?),
+            15 => MovementCommand::ElapseGarbageFrames(self.read_usize()?),
+            _ => return Err(ReplayDecodeError::InvalidValue),
+        })
+    }
+    fn read_result(&mut self) -> Result<ReplayResult, ReplayDecodeError> {
+        Ok(ReplayResult {
+            locked_minos: self.read_usize()?,
+            cleared_lines: self.read_usize()?,
+            max_combo: self.read_usize()?,
+            game_over: match self.read_u8()? {
+                0 => None,
+                1 => Some(GameOver::BlockOut),
+                2 => Some(GameOver::LockOut),
+                3 => Some(GameOver::TopOut),
+                4 => Some(GameOver::OutOfMinos),
+                _ => return Err(ReplayDecodeError::InvalidValue),
+            },
+            field_checksum: self.read_bits(64)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayRecorder, TetrisManager};
+
+    fn record(tetris_config: TetrisConfig, randomizer: MinoRandomizer) -> Replay {
+        let mut recorder = ReplayRecorder::with_randomizer(tetris_config, randomizer, 42, 10, 42);
+        let commands = [
+            MovementCommand::Left,
+            MovementCommand::RotateClockWise,
+            MovementCommand::HardDrop,
+            MovementCommand::Hold,
+            MovementCommand::Right,
+            MovementCommand::Right,
+            MovementCommand::Rotate180,
+            MovementCommand::Down,
+            MovementCommand::Lock,
+            MovementCommand::Attacked(AttackedLine {
+                hole_indexes: Some(alloc::vec![3]),
+                can_be_cleared: true,
+            }),
+            MovementCommand::Attacked(AttackedLine {
+                hole_indexes: None,
+                can_be_cleared: false,
+            }),
+            MovementCommand::CancelGarbage(1),
+            MovementCommand::ElapseGarbageFrames(300),
+            MovementCommand::InitialRotate(RotationType::CounterClockwise),
+            MovementCommand::InitialHold,
+            MovementCommand::RotateCounterClockWise,
+            MovementCommand::HardDrop,
+        ];
+        //timestampは減少しても欠けていてもよい
+        let timestamps = [Some(0), Some(17), None, Some(5), Some(1 << 40)];
+        for (i, movement_command) in commands.into_iter().enumerate() {
+            let _ = recorder.command(movement_command, timestamps[i % timestamps.len()]);
+        }
+        recorder.finish()
+    }
+    //本体を書き換えた後にチェックサムを付け直す
+    fn with_checksum(mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes.truncate(bytes.len() - CHECKSUM_SIZE);
+        let checksum = fnv1a(bytes.iter().copied());
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = record(TetrisConfig::default(), MinoRandomizer::default());
+        let bytes = replay.to_bytes();
+        assert!(bytes.starts_with(b"TCRP"));
+        assert_eq!(bytes[4], REPLAY_FORMAT_VERSION);
+        let decoded = Replay::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, replay);
+        assert_eq!(decoded.verify(), Ok(replay.result.unwrap()));
+    }
+
+    #[test]
+    fn round_trip_with_custom_config() {
+        let tetris_config = TetrisConfig {
+            move_reset_limit: None,
+            appearance_position: (2, 18),
+            can_hold_infinity: true,
+            spin_rule: SpinRule::AllMini,
+            preview_count: 3,
+            rotation_system: RotationSystem::Custom(RotationSystem::Ars.table().clone()),
+            rotate180_kicks: Rotate180Kicks::Nullpomino,
+            garbage_delay: GarbageDelay::Frames(60),
+            garbage_cap: Some(8),
+            is_garbage_blocked_by_line_clear: false,
+            visible_height: 22,
+            lock_out_rule: LockOutRule::PartialLockOut,
+        };
+        let replay = record(
+            tetris_config,
+            MinoRandomizer::History(HistoryRandomizer::tgm2()),
+        );
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+
+        //途中まで進んだbagの状態も復元される
+        let mut replay = Replay::new(TetrisConfig::default(), 7, 10, 42);
+        let tetris_manager = TetrisManager::new(TetrisConfig::default(), &7u64, 10, 42);
+        replay.start =
+            ReplayStart::Randomizer(tetris_manager.mino_queue.randomizer.clone().unwrap());
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+    }
+
+    #[test]
+    fn round_trip_with_start() {
+        let mut recorder = ReplayRecorder::with_sequence(
+            TetrisConfig::default(),
+            &[MinoType::MinoT, MinoType::MinoS],
+            Some(MinoType::MinoI),
+            Some(MinoRandomizer::default()),
+            3,
+            10,
+            42,
+        )
+        .unwrap();
+        let _ = recorder.command(MovementCommand::HardDrop, None);
+        let replay = recorder.finish();
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+
+        let mut field = alloc::vec![alloc::vec![Cell::Empty; 6]; 30];
+        field[29][0] = Cell::Obstruction(true);
+        field[29][1] = Cell::MinoBlock(MinoType::MinoZ);
+        field[28][5] = Cell::Wall;
+        let position = Position {
+            field,
+            direction: crate::MinoDirection::West,
+            location: Some((2, 20)),
+            hold: Some(MinoType::MinoO),
+            has_held: true,
+            queue: alloc::vec![MinoType::MinoJ, MinoType::MinoL],
+            randomizer: None,
+            combo: 2,
+            b2b: 1,
+            ..Position::new(Vec::new(), MinoType::MinoT)
+        };
+        let tetris_config = TetrisConfig {
+            appearance_position: (1, 19),
+            ..TetrisConfig::default()
+        };
+        let mut recorder = ReplayRecorder::from_position(tetris_config, position, 5).unwrap();
+        let _ = recorder.command(MovementCommand::HardDrop, Some(3));
+        let replay = recorder.finish();
+        assert_eq!((replay.width, replay.height), (6, 30));
+        let decoded = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(decoded, replay);
+        assert!(decoded.verify().is_ok());
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let bytes = record(TetrisConfig::default(), MinoRandomizer::default()).to_bytes();
+        for i in MAGIC.len()..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 0x10;
+            assert_eq!(
+                Replay::from_bytes(&corrupted),
+                Err(ReplayDecodeError::ChecksumMismatch)
+            );
+        }
+        assert_eq!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ReplayDecodeError::ChecksumMismatch)
+        );
+        assert_eq!(
+            Replay::from_bytes(b"TCRP"),
+            Err(ReplayDecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Replay::from_bytes(b"not a replay"),
+            Err(ReplayDecodeError::InvalidMagic)
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let bytes = record(TetrisConfig::default(), MinoRandomizer::default()).to_bytes();
+        let mut other_format = bytes.clone();
+        other_format[4] = REPLAY_FORMAT_VERSION + 1;
+        assert_eq!(
+            Replay::from_bytes(&with_checksum(other_format)),
+            Err(ReplayDecodeError::UnsupportedFormatVersion(
+                REPLAY_FORMAT_VERSION + 1
+            ))
+        );
+        //crateのバージョンの先頭の文字を書き換える
+        let mut other_crate = bytes;
+        other_crate[6] = b'9';
+        let mut version = String::from(CRATE_VERSION);
+        version.replace_range(..1, "9");
+        assert_eq!(
+            Replay::from_bytes(&with_checksum(other_crate)),
+            Err(ReplayDecodeError::CrateVersionMismatch(version))
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_would_panic() {
+        let invalid_replays = [
+            Replay::new(TetrisConfig::default(), 0, 0, 42),
+            Replay::new(TetrisConfig::default(), 0, 10, MAX_FIELD_SIZE + 1),
+            Replay::new(
+                TetrisConfig {
+                    preview_count: MAX_PREVIEW_COUNT + 1,
+                    ..TetrisConfig::default()
+                },
+                0,
+                10,
+                42,
+            ),
+            Replay::new(
+                TetrisConfig {
+                    appearance_position: (i64::MAX, 19),
+                    ..TetrisConfig::default()
+                },
+                0,
+                10,
+                42,
+            ),
+        ];
+        for copies in [0, 3] {
+            let randomizer = MinoRandomizer::Bag(BagRandomizer {
+                copies,
+                bag: heapless::Vec::new(),
+                position: 0,
+            });
+            let replay = Replay::with_randomizer(TetrisConfig::default(), randomizer, 0, 10, 42);
+            assert_eq!(
+                Replay::from_bytes(&replay.to_bytes()),
+                Err(ReplayDecodeError::InvalidValue)
+            );
+        }
+        for replay in invalid_replays {
+            assert_eq!(
+                Replay::from_bytes(&replay.to_bytes()),
+                Err(ReplayDecodeError::InvalidValue)
+            );
+        }
+        let mut rotation_table = RotationSystem::Srs.table().clone();
+        rotation_table.rotations[MinoType::MinoT][crate::MinoDirection::East] = [[0; 4]; 4];
+        let replay = Replay::new(
+            TetrisConfig {
+                rotation_system: RotationSystem::Custom(rotation_table),
+                ..TetrisConfig::default()
+            },
+            0,
+            10,
+            42,
+        );
+        assert_eq!(
+            Replay::from_bytes(&replay.to_bytes()),
+            Err(ReplayDecodeError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn too_narrow_field_is_blocked_out() {
+        let replay = Replay::from_bytes(
+            &record(TetrisConfig::default(), MinoRandomizer::default()).to_bytes(),
+        )
+        .unwrap();
+        let narrow = Replay { width: 2, ..replay };
+        let decoded = Replay::from_bytes(&narrow.to_bytes()).unwrap();
+        assert_eq!(decoded.play().unwrap().game_over, Some(GameOver::BlockOut));
+    }
+}
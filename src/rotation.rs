@@ -0,0 +1,652 @@
+use crate::MinoDirection::{self, *};
+use crate::MinoType::{self, *};
+use crate::RotationType;
+use enum_map::{EnumMap, enum_map};
+use once_cell::sync::Lazy;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "alloc"))]
+use crate::NO_ALLOC_KICK_CAPACITY;
+
+type OffsetsType = EnumMap<MinoDirection, EnumMap<RotationType, &'static [(i64, i64)]>>;
+type RotationsType = EnumMap<MinoType, EnumMap<MinoDirection, [[i64; 4]; 4]>>;
+
+#[cfg(feature = "alloc")]
+type KickOffsets = alloc::vec::Vec<(i64, i64)>;
+#[cfg(not(feature = "alloc"))]
+type KickOffsets = heapless::Vec<(i64, i64), NO_ALLOC_KICK_CAPACITY>;
+
+//ミノの形と壁蹴りのオフセット(x,y上向きが正)
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct RotationTable {
+    pub rotations: RotationsType,
+    pub offsets: EnumMap<MinoType, EnumMap<MinoDirection, EnumMap<RotationType, KickOffsets>>>,
+    pub center_column_rule: EnumMap<MinoType, bool>,
+}
+impl RotationTable {
+    #[allow(clippy::iter_cloned_collect)] //no_allocではheapless::Vecにcollectする
+    fn from_statics(
+        rotations: &RotationsType,
+        offsets: impl Fn(MinoType) -> &'static OffsetsType,
+        center_column_minos: &[MinoType],
+    ) -> Self {
+        Self {
+            rotations: *rotations,
+            offsets: EnumMap::from_fn(|mino_type| {
+                EnumMap::from_fn(|mino_direction| {
+                    EnumMap::from_fn(|rotation_type| {
+                        offsets(mino_type)[mino_direction][rotation_type]
+                            .iter()
+                            .copied()
+                            .collect()
+                    })
+                })
+            }),
+            center_column_rule: EnumMap::from_fn(|mino_type| {
+                center_column_minos.contains(&mino_type)
+            }),
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Default)]
+#[allow(clippy::large_enum_variant)]
+pub enum RotationSystem {
+    #[default]
+    Srs,
+    SrsPlus, //TETR.IO
+    Ars,     //TGM
+    Nrs,     //NES
+    NoKick,
+    Custom(RotationTable),
+}
+impl RotationSystem {
+    pub fn table(&self) -> &RotationTable {
+        match self {
+            Self::Srs => &SRS,
+            Self::SrsPlus => &SRS_PLUS,
+            Self::Ars => &ARS,
+            Self::Nrs => &NRS,
+            Self::NoKick => &NO_KICK,
+            Self::Custom(rotation_table) => rotation_table,
+        }
+    }
+    pub(crate) fn rotations(&self, mino_type: MinoType) -> EnumMap<MinoDirection, [[i64; 4]; 4]> {
+        self.table().rotations[mino_type]
+    }
+    pub(crate) fn offsets(
+        &self,
+        mino_type: MinoType,
+        mino_direction: MinoDirection,
+        rotation_type: RotationType,
+    ) -> &[(i64, i64)] {
+        &self.table().offsets[mino_type][mino_direction][rotation_type]
+    }
+    //最後の壁蹴り(TST,fin)によるT-spin miniをfullとして扱うのはSRS系だけ
+    pub(crate) fn upgrades_last_kick_t_spin(&self) -> bool {
+        matches!(self, Self::Srs | Self::SrsPlus)
+    }
+    //回転先で最初に衝突したブロックが中央の列にあるなら壁蹴りしない(ARSのJ,L,T)
+    pub(crate) fn has_center_column_rule(&self, mino_type: MinoType) -> bool {
+        self.table().center_column_rule[mino_type]
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
+pub enum Rotate180Kicks {
+    Disabled, //180度回転は常に失敗する
+    NoKick,
+    #[default]
+    RotationSystem, //RotationTableのRotate180を使う(TETR.IOの180度回転はSrsPlusのもの)
+    Nullpomino,
+}
+impl Rotate180Kicks {
+    pub(crate) fn offsets<'a>(
+        &self,
+        rotation_system: &'a RotationSystem,
+        mino_type: MinoType,
+        mino_direction: MinoDirection,
+    ) -> Option<&'a [(i64, i64)]> {
+        match self {
+            Self::Disabled => None,
+            Self::NoKick => Some(&[(0, 0)]),
+            Self::RotationSystem => {
+                Some(rotation_system.offsets(mino_type, mino_direction, RotationType::Rotate180))
+            }
+            Self::Nullpomino => Some(match mino_type {
+                MinoI => OFFSETS_180_NULLPOMINO_MINO_I[mino_direction],
+                _ => OFFSETS_180_NULLPOMINO[mino_direction],
+            }),
+        }
+    }
+}
+
+static SRS: Lazy<RotationTable> = Lazy::new(|| {
+    RotationTable::from_statics(
+        &ROTATIONS,
+        |mino_type| match mino_type {
+            MinoI => &OFFSETS_MINO_I,
+            _ => &OFFSETS,
+        },
+        &[],
+    )
+});
+static SRS_PLUS: Lazy<RotationTable> = Lazy::new(|| {
+    RotationTable::from_statics(
+        &ROTATIONS,
+        |mino_type| match mino_type {
+            MinoI => &SRS_PLUS_OFFSETS_MINO_I,
+            _ => &SRS_PLUS_OFFSETS,
+        },
+        &[],
+    )
+});
+static ARS: Lazy<RotationTable> = Lazy::new(|| {
+    RotationTable::from_statics(
+        &ARS_ROTATIONS,
+        |mino_type| match mino_type {
+            MinoI => &NO_KICK_OFFSETS,
+            _ => &ARS_OFFSETS,
+        },
+        &[MinoJ, MinoL, MinoT],
+    )
+});
+static NRS: Lazy<RotationTable> =
+    Lazy::new(|| RotationTable::from_statics(&NRS_ROTATIONS, |_| &NO_KICK_OFFSETS, &[]));
+static NO_KICK: Lazy<RotationTable> =
+    Lazy::new(|| RotationTable::from_statics(&ROTATIONS, |_| &NO_KICK_OFFSETS, &[]));
+
+static ROTATIONS: Lazy<RotationsType> = Lazy::new(|| {
+    enum_map! {
+        MinoT => enum_map! {
+            North => [[0, 1, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East => [[0, 1, 0, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoS=>enum_map! {
+            North=>[[0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            West=>[[1, 0, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoZ=>enum_map!{
+            North=>[[1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 0, 1, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [1, 1, 0, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoL=>enum_map! {
+            North=>[[0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 1, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
+            West=>[[1, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoJ=>enum_map!{
+            North=>[[1, 0, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 1, 1, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 0, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoO=>enum_map!{
+            North=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            East=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+        },
+        MinoI=>enum_map!{
+            North=>[[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0]],
+            South=>[[0, 0, 0, 0], [0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0]],
+        }
+    }
+});
+
+static OFFSETS: Lazy<OffsetsType> = Lazy::new(|| {
+    enum_map! {
+        North=>enum_map!{
+                RotationType::Clockwise=>[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)].as_slice(),
+                RotationType::CounterClockwise=>[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)].as_slice(),
+                RotationType::Rotate180=>[(1, 0),(2, 0),(1, 1),(2, 1),(-1, 0),(-2, 0),(-1, 1),(-2, 1),(0, -1),(3, 0),(-3, 0)].as_slice(),
+        },
+        East=>enum_map!{
+                RotationType::Clockwise=>[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)].as_slice(),
+                RotationType::CounterClockwise=>[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)].as_slice(),
+                RotationType::Rotate180=> [(0, 1), (0, 2), (-1, 1), (-1, 2), (0, -1), (0, -2), (-1, -1), (-1, -2), (1, 0), (0, 3), (0, -3),].as_slice(),
+        },
+        South=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)].as_slice(),
+            RotationType::Rotate180=>[(-1, 0),(-2, 0),(-1, -1),(-2, -1),(1, 0),(2, 0), (1, -1),(2, -1),(0, 1),(-3, 0),(3, 0)].as_slice(),
+        },
+        West=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)].as_slice(),
+            RotationType::Rotate180=>[(0, 1),(0, 2),(1, 1),(1, 2),(0, -1),(0, -2),(1, -1),(1, -2),(-1, 0),(0, 3),(0, -3)].as_slice(),
+        }
+    }
+});
+
+static OFFSETS_MINO_I: Lazy<OffsetsType> = Lazy::new(|| {
+    enum_map! {
+        North=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)].as_slice(),
+            RotationType::Rotate180=>[(-1, 0), (-2, 0), (1, 0), (2, 0), (0, 1)].as_slice(),
+        },
+        East=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)].as_slice(),
+            RotationType::Rotate180=>[(0, 1), (0, 2), (0, -1), (0, -2), (-1, 0)].as_slice(),
+        },
+        South=>enum_map! {
+            RotationType::Clockwise=>[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)].as_slice(),
+            RotationType::Rotate180=>[(1, 0), (2, 0), (-1, 0), (-2, 0), (0, -1)].as_slice(),
+        },
+        West=>enum_map! {
+            RotationType::Clockwise=> [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)].as_slice(),
+            RotationType::Rotate180=>[(0, 1), (0, 2), (0, -1), (0, -2), (1, 0)].as_slice(),
+        },
+    }
+});
+
+//TETR.IOのSRS+の180度回転(SRSのRotate180より候補が少なく、(0,0)から試す)
+static OFFSETS_180_SRS_PLUS: Lazy<EnumMap<MinoDirection, &'static [(i64, i64)]>> =
+    Lazy::new(|| {
+        enum_map! {
+            North=>[(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)].as_slice(),
+            East=>[(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)].as_slice(),
+            South=>[(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)].as_slice(),
+            West=>[(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)].as_slice(),
+        }
+    });
+
+static OFFSETS_180_NULLPOMINO: Lazy<EnumMap<MinoDirection, &'static [(i64, i64)]>> = Lazy::new(
+    || {
+        enum_map! {
+            North=>[(0, 0), (1, 0), (2, 0), (1, -1), (2, -1), (-1, 0), (-2, 0), (-1, -1), (-2, -1), (0, 1), (3, 0), (-3, 0)].as_slice(),
+            East=>[(0, 0), (0, -1), (0, -2), (-1, -1), (-1, -2), (0, 1), (0, 2), (-1, 1), (-1, 2), (1, 0), (0, -3), (0, 3)].as_slice(),
+            South=>[(0, 0), (-1, 0), (-2, 0), (-1, 1), (-2, 1), (1, 0), (2, 0), (1, 1), (2, 1), (0, -1), (-3, 0), (3, 0)].as_slice(),
+            West=>[(0, 0), (0, -1), (0, -2), (1, -1), (1, -2), (0, 1), (0, 2), (1, 1), (1, 2), (-1, 0), (0, -3), (0, 3)].as_slice(),
+        }
+    },
+);
+
+static OFFSETS_180_NULLPOMINO_MINO_I: Lazy<EnumMap<MinoDirection, &'static [(i64, i64)]>> =
+    Lazy::new(|| {
+        enum_map! {
+            North=>[(0, 0), (-1, 0), (-2, 0), (1, 0), (2, 0), (0, -1)].as_slice(),
+            East=>[(0, 0), (0, -1), (0, -2), (0, 1), (0, 2), (-1, 0)].as_slice(),
+            South=>[(0, 0), (1, 0), (2, 0), (-1, 0), (-2, 0), (0, 1)].as_slice(),
+            West=>[(0, 0), (0, -1), (0, -2), (0, 1), (0, 2), (1, 0)].as_slice(),
+        }
+    });
+
+static SRS_PLUS_OFFSETS: Lazy<OffsetsType> = Lazy::new(|| {
+    let mut offsets = *OFFSETS;
+    for (mino_direction, offsets) in offsets.iter_mut() {
+        offsets[RotationType::Rotate180] = OFFSETS_180_SRS_PLUS[mino_direction];
+    }
+    offsets
+});
+
+static SRS_PLUS_OFFSETS_MINO_I: Lazy<OffsetsType> = Lazy::new(|| {
+    enum_map! {
+        North=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)].as_slice(),
+            RotationType::Rotate180=>OFFSETS_180_SRS_PLUS[North],
+        },
+        East=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)].as_slice(),
+            RotationType::Rotate180=>OFFSETS_180_SRS_PLUS[East],
+        },
+        South=>enum_map! {
+            RotationType::Clockwise=>[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)].as_slice(),
+            RotationType::Rotate180=>OFFSETS_180_SRS_PLUS[South],
+        },
+        West=>enum_map! {
+            RotationType::Clockwise=>[(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)].as_slice(),
+            RotationType::Rotate180=>OFFSETS_180_SRS_PLUS[West],
+        },
+    }
+});
+
+static ARS_OFFSETS: Lazy<OffsetsType> = Lazy::new(|| {
+    enum_map! {
+        _=>enum_map!{
+            RotationType::Clockwise=>[(0, 0), (1, 0), (-1, 0)].as_slice(),
+            RotationType::CounterClockwise=>[(0, 0), (1, 0), (-1, 0)].as_slice(),
+            RotationType::Rotate180=>[(0, 0)].as_slice(),
+        },
+    }
+});
+
+static NO_KICK_OFFSETS: Lazy<OffsetsType> = Lazy::new(|| {
+    enum_map! {
+        _=>enum_map!{
+            _=>[(0, 0)].as_slice(),
+        },
+    }
+});
+
+static ARS_ROTATIONS: Lazy<RotationsType> = Lazy::new(|| {
+    enum_map! {
+        MinoT => enum_map! {
+            North => [[0, 0, 0, 0], [1, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            East => [[0, 1, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [0, 1, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoS=>enum_map! {
+            North=>[[0, 0, 0, 0], [0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            East=>[[1, 0, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            West=>[[1, 0, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoZ=>enum_map!{
+            North=>[[0, 0, 0, 0], [1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            East=>[[0, 0, 1, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 0, 1, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoL=>enum_map! {
+            North=>[[0, 0, 0, 0], [1, 1, 1, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[1, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+        },
+        MinoJ=>enum_map!{
+            North=>[[0, 0, 0, 0], [1, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
+            East=>[[0, 1, 0, 0], [0, 1, 0, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 0, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 1, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoO=>enum_map!{
+            _=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+        },
+        MinoI=>enum_map!{
+            North=>[[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0]],
+            West=>[[0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0]],
+        }
+    }
+});
+
+static NRS_ROTATIONS: Lazy<RotationsType> = Lazy::new(|| {
+    enum_map! {
+        MinoT => enum_map! {
+            North => [[0, 0, 0, 0], [1, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            East => [[0, 1, 0, 0], [1, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 1, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoS=>enum_map! {
+            North=>[[0, 0, 0, 0], [0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            East=>[[0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
+        },
+        MinoZ=>enum_map!{
+            North=>[[0, 0, 0, 0], [1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            East=>[[0, 0, 1, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 0, 0], [1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+            West=>[[0, 0, 1, 0], [0, 1, 1, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoL=>enum_map! {
+            North=>[[0, 0, 0, 0], [1, 1, 1, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
+            East=>[[1, 1, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 0, 0], [0, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+        },
+        MinoJ=>enum_map!{
+            North=>[[0, 0, 0, 0], [1, 1, 1, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
+            East=>[[0, 1, 0, 0], [0, 1, 0, 0], [1, 1, 0, 0], [0, 0, 0, 0]],
+            South=>[[1, 0, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+            West=>[[0, 1, 1, 0], [0, 1, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]],
+        },
+        MinoO=>enum_map!{
+            _=>[[0, 0, 0, 0], [0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0]],
+        },
+        MinoI=>enum_map!{
+            North=>[[0, 0, 0, 0], [0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0]],
+            East=>[[0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0]],
+            South=>[[0, 0, 0, 0], [0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0]],
+            West=>[[0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0], [0, 0, 1, 0]],
+        }
+    }
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, Field, Mino, SpinKind, SpinRule, parse_board};
+
+    #[cfg(feature = "alloc")]
+    fn parse(text: &str) -> Field {
+        use crate::DEFAULT_BOARD_SIZE;
+        parse_board(text, DEFAULT_BOARD_SIZE.0, DEFAULT_BOARD_SIZE.1).unwrap()
+    }
+    #[cfg(not(feature = "alloc"))]
+    fn parse(text: &str) -> Field {
+        parse_board(text).unwrap()
+    }
+    fn rotate(
+        mino: &mut Mino,
+        rotation_type: RotationType,
+        field: &Field,
+        rotation_system: &RotationSystem,
+    ) -> bool {
+        mino.rotate(
+            rotation_type,
+            field,
+            None,
+            rotation_system,
+            Rotate180Kicks::RotationSystem,
+            SpinRule::TOnly,
+        )
+    }
+
+    #[test]
+    fn srs_plus_mino_i_kicks_are_symmetric() {
+        let srs_plus = RotationSystem::SrsPlus;
+        assert_eq!(
+            srs_plus.offsets(MinoI, North, RotationType::Clockwise),
+            [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)]
+        );
+        assert_eq!(
+            srs_plus.offsets(MinoI, North, RotationType::CounterClockwise),
+            [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)]
+        );
+        assert_eq!(
+            RotationSystem::Srs.offsets(MinoI, North, RotationType::Clockwise),
+            [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]
+        );
+        //I以外の90度回転はSRSと同じ
+        for mino_direction in [North, East, South, West] {
+            for rotation_type in [RotationType::Clockwise, RotationType::CounterClockwise] {
+                assert_eq!(
+                    srs_plus.offsets(MinoT, mino_direction, rotation_type),
+                    RotationSystem::Srs.offsets(MinoT, mino_direction, rotation_type)
+                );
+            }
+        }
+        assert_eq!(
+            srs_plus.offsets(MinoT, North, RotationType::Rotate180),
+            [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]
+        );
+    }
+
+    #[test]
+    fn ars_kicks_right_then_left() {
+        let ars = RotationSystem::Ars;
+        let field = parse("");
+        //West向きのTを左の壁に付けてからNorthに回転すると右に1マス蹴られる
+        let mut mino = Mino::with_direction(MinoT, -1, 20, West, &field, &ars).unwrap();
+        assert!(rotate(&mut mino, RotationType::Clockwise, &field, &ars));
+        assert_eq!((mino.x, mino.y, mino.mino_direction), (0, 20, North));
+        assert_eq!(mino.last_kick_index, Some(1));
+    }
+
+    #[test]
+    fn ars_center_column_rule_prevents_kick() {
+        let ars = RotationSystem::Ars;
+        let mut field = parse("");
+        field[20][4] = Cell::Obstruction(true);
+        let mut mino = Mino::new(MinoT, 3, 20, &field, &ars, None).unwrap();
+        assert!(!rotate(&mut mino, RotationType::Clockwise, &field, &ars));
+        //中央の列以外で衝突するなら壁蹴りする
+        field[20][4] = Cell::Empty;
+        field[20][3] = Cell::Obstruction(true);
+        let mut mino = Mino::new(MinoL, 3, 20, &field, &ars, None).unwrap();
+        assert!(rotate(&mut mino, RotationType::Clockwise, &field, &ars));
+        assert_eq!((mino.x, mino.mino_direction), (4, East));
+    }
+
+    #[test]
+    fn rotate180_kicks_on_floor() {
+        let srs = RotationSystem::Srs;
+        let field = parse("");
+        //床に接したTを180度回転させると床に埋まるので上に蹴られる
+        let rotate180 = |rotation_system: &RotationSystem, rotate180_kicks: Rotate180Kicks| {
+            let mut mino = Mino::new(MinoT, 3, 40, &field, rotation_system, None).unwrap();
+            let is_rotated = mino.rotate(
+                RotationType::Rotate180,
+                &field,
+                None,
+                rotation_system,
+                rotate180_kicks,
+                SpinRule::TOnly,
+            );
+            is_rotated.then_some((mino.x, mino.y, mino.mino_direction, mino.last_kick_index))
+        };
+        assert_eq!(rotate180(&srs, Rotate180Kicks::Disabled), None);
+        assert_eq!(rotate180(&srs, Rotate180Kicks::NoKick), None);
+        assert_eq!(
+            rotate180(&srs, Rotate180Kicks::Nullpomino),
+            Some((3, 39, South, Some(9)))
+        );
+        //SRSのRotate180は横に蹴ってから上に蹴る
+        assert_eq!(
+            rotate180(&RotationSystem::Srs, Rotate180Kicks::RotationSystem),
+            Some((4, 39, South, Some(2)))
+        );
+        //SRS+のRotate180は真上に蹴る
+        assert_eq!(
+            rotate180(&RotationSystem::SrsPlus, Rotate180Kicks::RotationSystem),
+            Some((3, 39, South, Some(1)))
+        );
+    }
+
+    #[test]
+    fn rotate180_tables_of_srs_and_srs_plus() {
+        assert_eq!(
+            RotationSystem::Srs.offsets(MinoT, North, RotationType::Rotate180),
+            [
+                (1, 0),
+                (2, 0),
+                (1, 1),
+                (2, 1),
+                (-1, 0),
+                (-2, 0),
+                (-1, 1),
+                (-2, 1),
+                (0, -1),
+                (3, 0),
+                (-3, 0)
+            ]
+        );
+        assert_eq!(
+            RotationSystem::Srs.offsets(MinoI, North, RotationType::Rotate180),
+            [(-1, 0), (-2, 0), (1, 0), (2, 0), (0, 1)]
+        );
+        //SRS+はIも含めて全てのミノで同じ表を使う
+        for mino_type in [MinoT, MinoI] {
+            assert_eq!(
+                RotationSystem::SrsPlus.offsets(mino_type, North, RotationType::Rotate180),
+                [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]
+            );
+        }
+    }
+
+    #[test]
+    fn rotate180_kicks_of_mino_i() {
+        assert_eq!(
+            Rotate180Kicks::Nullpomino.offsets(&RotationSystem::Srs, MinoI, East),
+            Some([(0, 0), (0, -1), (0, -2), (0, 1), (0, 2), (-1, 0)].as_slice())
+        );
+        assert_eq!(
+            Rotate180Kicks::RotationSystem.offsets(&RotationSystem::SrsPlus, MinoI, East),
+            Some([(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)].as_slice())
+        );
+        assert_eq!(
+            Rotate180Kicks::NoKick.offsets(&RotationSystem::Srs, MinoI, East),
+            Some([(0, 0)].as_slice())
+        );
+    }
+
+    #[test]
+    fn srs_last_kick_upgrades_t_spin_mini() {
+        let srs = RotationSystem::Srs;
+        let field = parse(
+            "
+            .X..XXX..X
+            X...X.X...
+            X.XXX.XXX.
+            .XX..XX...
+            .X...X....",
+        );
+        let rotate_ccw = |spin_rule: SpinRule| {
+            let mut mino = Mino::with_direction(MinoT, 7, 37, North, &field, &srs).unwrap();
+            assert!(mino.rotate(
+                RotationType::CounterClockwise,
+                &field,
+                None,
+                &srs,
+                Rotate180Kicks::RotationSystem,
+                spin_rule,
+            ));
+            assert_eq!((mino.x, mino.y, mino.last_kick_index), (8, 39, Some(4)));
+            mino.last_spin
+        };
+        assert_eq!(rotate_ccw(SpinRule::TOnly), SpinKind::Mini);
+        assert_eq!(rotate_ccw(SpinRule::Guideline), SpinKind::Full);
+    }
+
+    #[test]
+    fn ars_last_kick_does_not_upgrade_t_spin_mini() {
+        let ars = RotationSystem::Ars;
+        let field = parse(
+            "
+            ...X......
+            ......X...
+            ...X.X....
+            ..........",
+        );
+        //ARSの壁蹴りでは回転前の位置と重なるので、回転前のミノは空のフィールドで作る
+        let mut mino = Mino::with_direction(MinoT, 4, 38, South, &parse(""), &ars).unwrap();
+        assert!(mino.rotate(
+            RotationType::Clockwise,
+            &field,
+            None,
+            &ars,
+            Rotate180Kicks::RotationSystem,
+            SpinRule::Guideline,
+        ));
+        assert_eq!((mino.x, mino.y, mino.mino_direction), (3, 38, West));
+        assert_eq!(mino.last_kick_index, Some(2));
+        assert_eq!(mino.last_spin, SpinKind::Mini);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{LineClear, SpinKind};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub trait ScoringRule {
+    fn line_clear_score(&self, line_clear: &LineClear, level: u64) -> u64;
+    fn soft_drop_score(&self, _distance: usize) -> u64 {
+        0
+    }
+    fn hard_drop_score(&self, _distance: usize) -> u64 {
+        0
+    }
+    //固定されたときの得点(hard dropの得点を含む)
+    fn lock_score(&self, line_clear: &LineClear, level: u64) -> u64 {
+        self.line_clear_score(line_clear, level)
+            + self.hard_drop_score(line_clear.hard_drop_distance)
+    }
+}
+
+// levelは1から始まる
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct GuidelineScoring;
+impl ScoringRule for GuidelineScoring {
+    fn line_clear_score(&self, line_clear: &LineClear, level: u64) -> u64 {
+        let lines = line_clear.cleared_line_count;
+        let base = match (line_clear.spin, lines) {
+            (SpinKind::None, 0) => 0,
+            (SpinKind::None, 1) => 100,
+            (SpinKind::None, 2) => 300,
+            (SpinKind::None, 3) => 500,
+            (SpinKind::None, _) => 800,
+            (SpinKind::Mini, 0) => 100,
+            (SpinKind::Mini, 1) => 200,
+            (SpinKind::Mini, _) => 400,
+            (SpinKind::Full, 0) => 400,
+            (SpinKind::Full, 1) => 800,
+            (SpinKind::Full, 2) => 1200,
+            (SpinKind::Full, _) => 1600,
+        };
+        let is_b2b = line_clear.is_difficult() && line_clear.b2b >= 2;
+        let mut score = if is_b2b { base * 3 / 2 } else { base };
+        if lines > 0 && line_clear.combo >= 2 {
+            score += 50 * (line_clear.combo as u64 - 1);
+        }
+        if lines > 0 && line_clear.is_perfect {
+            score += match lines {
+                1 => 800,
+                2 => 1200,
+                3 => 1800,
+                _ if is_b2b => 3200,
+                _ => 2000,
+            };
+        }
+        score * level
+    }
+    fn soft_drop_score(&self, distance: usize) -> u64 {
+        distance as u64
+    }
+    fn hard_drop_score(&self, distance: usize) -> u64 {
+        distance as u64 * 2
+    }
+}
+
+// levelは0から始まる
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct NesScoring;
+impl ScoringRule for NesScoring {
+    fn line_clear_score(&self, line_clear: &LineClear, level: u64) -> u64 {
+        let base = match line_clear.cleared_line_count {
+            0 => 0,
+            1 => 40,
+            2 => 100,
+            3 => 300,
+            _ => 1200,
+        };
+        base * (level + 1)
+    }
+    fn soft_drop_score(&self, distance: usize) -> u64 {
+        distance as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_clear;
+
+    #[test]
+    fn guideline_line_clears() {
+        let scoring = GuidelineScoring;
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(0, SpinKind::None, 0, 0), 1),
+            0
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::None, 1, 0), 1),
+            100
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(2, SpinKind::None, 1, 0), 1),
+            300
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(3, SpinKind::None, 1, 0), 1),
+            500
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(4, SpinKind::None, 1, 1), 1),
+            800
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(4, SpinKind::None, 1, 1), 3),
+            2400
+        );
+    }
+
+    #[test]
+    fn guideline_spins() {
+        let scoring = GuidelineScoring;
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(0, SpinKind::Mini, 0, 0), 1),
+            100
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::Mini, 1, 1), 1),
+            200
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(2, SpinKind::Mini, 1, 1), 1),
+            400
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(0, SpinKind::Full, 0, 0), 1),
+            400
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::Full, 1, 1), 1),
+            800
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(2, SpinKind::Full, 1, 1), 1),
+            1200
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(3, SpinKind::Full, 1, 1), 1),
+            1600
+        );
+    }
+
+    #[test]
+    fn guideline_b2b_and_combo() {
+        let scoring = GuidelineScoring;
+        //B2Bは1.5倍
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(4, SpinKind::None, 1, 2), 1),
+            1200
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(2, SpinKind::Full, 1, 3), 1),
+            1800
+        );
+        //B2B中でも通常のライン消去は1.5倍にならない
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::None, 1, 2), 1),
+            100
+        );
+        //REN数ごとに50点
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::None, 2, 0), 1),
+            150
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::None, 5, 0), 2),
+            600
+        );
+    }
+
+    #[test]
+    fn guideline_perfect_clears() {
+        let scoring = GuidelineScoring;
+        let perfect = |lines, b2b| LineClear {
+            is_perfect: true,
+            ..line_clear(lines, SpinKind::None, 1, b2b)
+        };
+        assert_eq!(scoring.line_clear_score(&perfect(1, 0), 1), 900);
+        assert_eq!(scoring.line_clear_score(&perfect(2, 0), 1), 1500);
+        assert_eq!(scoring.line_clear_score(&perfect(3, 0), 1), 2300);
+        assert_eq!(scoring.line_clear_score(&perfect(4, 1), 1), 2800);
+        assert_eq!(scoring.line_clear_score(&perfect(4, 2), 1), 4400);
+    }
+
+    #[test]
+    fn guideline_drops() {
+        let scoring = GuidelineScoring;
+        let line_clear = LineClear {
+            hard_drop_distance: 18,
+            ..line_clear(1, SpinKind::None, 1, 0)
+        };
+        assert_eq!(scoring.soft_drop_score(5), 5);
+        assert_eq!(scoring.hard_drop_score(5), 10);
+        assert_eq!(scoring.lock_score(&line_clear, 2), 236);
+    }
+
+    #[test]
+    fn nes_line_clears() {
+        let scoring = NesScoring;
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(1, SpinKind::None, 1, 0), 0),
+            40
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(2, SpinKind::None, 1, 0), 0),
+            100
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(3, SpinKind::None, 1, 0), 0),
+            300
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(4, SpinKind::None, 1, 1), 0),
+            1200
+        );
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(4, SpinKind::None, 1, 1), 9),
+            12000
+        );
+        //spinやB2Bは得点に影響しない
+        assert_eq!(
+            scoring.line_clear_score(&line_clear(2, SpinKind::Full, 3, 2), 1),
+            200
+        );
+        assert_eq!(scoring.hard_drop_score(10), 0);
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{
+    Buttons, GameOver, InputConfig, InputState, Key, LineClear, MinoRandomizer, MinoState,
+    MovementCommand, Randomizer, TetrisManager, initial_commands,
+};
+use core::time::Duration;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "alloc"))]
+use heapless::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "alloc")]
+type LineClears = Vec<LineClear>;
+#[cfg(not(feature = "alloc"))]
+type LineClears = Vec<LineClear, { crate::NO_ALLOC_LINE_CLEAR_CAPACITY }>;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimingConfig {
+    pub gravity: Duration,         //1マス落下する間隔(ZEROなら即座に接地する)
+    pub lock_delay: Duration,      //接地してから固定されるまでの時間
+    pub tick: Duration,            //1フレームの長さ(ZEROならフレームが進まない)
+    pub input_config: InputConfig, //DAS,ARR,DCD,ソフトドロップはフレーム数で指定する
+}
+impl Default for TimingConfig {
+    fn default() -> Self {
+        Self {
+            gravity: Duration::from_millis(1000),
+            lock_delay: Duration::from_millis(500),
+            tick: Duration::from_nanos(16_666_667),
+            input_config: InputConfig::default(),
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyEvent {
+    Pressed(Key),
+    Released(Key),
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimedTetrisManager<R = MinoRandomizer> {
+    tetris_manager: TetrisManager<R>,
+    timing_config: TimingConfig,
+    input_state: InputState,
+    held_keys: Buttons,
+    pressed_keys: Buttons, //次のフレームまでに押されたキー(1フレーム未満の入力を取りこぼさない)
+    mino_state: MinoState,
+    frame_timer: Duration, //まだフレームとして処理していない時間
+    gravity_timer: Duration,
+    lock_timer: Duration,
+}
+impl<R: Randomizer> TimedTetrisManager<R> {
+    pub fn new(mut tetris_manager: TetrisManager<R>, timing_config: TimingConfig) -> Self {
+        let mino_state = tetris_manager.get_mino_state();
+        Self {
+            tetris_manager,
+            input_state: InputState::new(timing_config.input_config),
+            timing_config,
+            held_keys: Buttons::default(),
+            pressed_keys: Buttons::default(),
+            mino_state,
+            frame_timer: Duration::ZERO,
+            gravity_timer: Duration::ZERO,
+            lock_timer: Duration::ZERO,
+        }
+    }
+    pub fn tick(&mut self, key_events: &[KeyEvent]) -> (Result<MinoState, GameOver>, LineClears) {
+        self.advance(self.timing_config.tick, key_events)
+    }
+    //key_eventsを記録した後にelapsedだけ時間を進め、経過したフレームを順に処理する
+    pub fn advance(
+        &mut self,
+        elapsed: Duration,
+        key_events: &[KeyEvent],
+    ) -> (Result<MinoState, GameOver>, LineClears) {
+        for &key_event in key_events {
+            match key_event {
+                KeyEvent::Pressed(key) => {
+                    self.held_keys[key] = true;
+                    self.pressed_keys[key] = true;
+                }
+                KeyEvent::Released(key) => self.held_keys[key] = false,
+            }
+        }
+        let mut line_clears = LineClears::new();
+        let tick = self.timing_config.tick;
+        self.frame_timer += elapsed;
+        while !tick.is_zero() && self.frame_timer >= tick && !self.is_game_over() {
+            self.frame_timer -= tick;
+            self.next_frame(&mut line_clears);
+        }
+        match self.tetris_manager.get_game_over() {
+            Some(game_over) => (Err(game_over), line_clears),
+            None => (Ok(self.mino_state), line_clears),
+        }
+    }
+    fn next_frame(&mut self, line_clears: &mut LineClears) {
+        let buttons = Buttons::from_fn(|key| self.held_keys[key] || self.pressed_keys[key]);
+        self.pressed_keys = Buttons::default();
+        let mut should_reset_lock_timer = false;
+        let mut input_state = core::mem::take(&mut self.input_state);
+        input_state.next_frame(&buttons, |movement_command| {
+            self.execute(movement_command, line_clears, &mut should_reset_lock_timer)
+        });
+        self.apply_gravity(line_clears, &mut should_reset_lock_timer);
+        if !should_reset_lock_timer && self.mino_state != MinoState::AirBorne {
+            self.lock_timer += self.timing_config.tick;
+            if self.lock_timer >= self.timing_config.lock_delay {
+                if self.timing_config.input_config.is_initial_action_enabled {
+                    for command in initial_commands(&buttons) {
+                        self.execute(command, line_clears, &mut should_reset_lock_timer);
+                    }
+                }
+                if self.execute(
+                    MovementCommand::Lock,
+                    line_clears,
+                    &mut should_reset_lock_timer,
+                ) {
+                    input_state.cut_das();
+                }
+            }
+        }
+        if should_reset_lock_timer {
+            self.lock_timer = Duration::ZERO;
+        }
+        self.input_state = input_state;
+        if !self.is_game_over() {
+            let _ = self
+                .tetris_manager
+                .command(MovementCommand::ElapseGarbageFrames(1));
+            self.mino_state = self.tetris_manager.get_mino_state();
+        }
+    }
+    //Attackedなどキー入力によらないコマンドを実行する
+    pub fn command(
+        &mut self,
+        movement_command: MovementCommand,
+    ) -> (Result<MinoState, GameOver>, LineClears) {
+        let mut line_clears = LineClears::new();
+        let mut should_reset_lock_timer = false;
+        self.execute(
+            movement_command,
+            &mut line_clears,
+            &mut should_reset_lock_timer,
+        );
+        if should_reset_lock_timer {
+            self.lock_timer = Duration::ZERO;
+        }
+        match self.tetris_manager.get_game_over() {
+            Some(game_over) => (Err(game_over), line_clears),
+            None => (Ok(self.mino_state), line_clears),
+        }
+    }
+    fn apply_gravity(&mut self, line_clears: &mut LineClears, should_reset_lock_timer: &mut bool) {
+        let gravity = self.timing_config.gravity;
+        if gravity.is_zero() {
+            while self.execute(MovementCommand::Down, line_clears, should_reset_lock_timer) {}
+            self.gravity_timer = Duration::ZERO;
+            return;
+        }
+        self.gravity_timer += self.timing_config.tick;
+        while self.gravity_timer >= gravity {
+            self.gravity_timer -= gravity;
+            if !self.execute(MovementCommand::Down, line_clears, should_reset_lock_timer) {
+                self.gravity_timer = Duration::ZERO;
+                break;
+            }
+        }
+    }
+    fn execute(
+        &mut self,
+        movement_command: MovementCommand,
+        line_clears: &mut LineClears,
+        should_reset_lock_timer: &mut bool,
+    ) -> bool {
+        if self.is_game_over() {
+            return false;
+        }
+        let is_hold = movement_command == MovementCommand::Hold;
+        let (result, line_clear, is_succeeded) = self.tetris_manager.command(movement_command);
+        if let Ok(mino_state) = result {
+            self.mino_state = mino_state;
+            match mino_state {
+                MinoState::JustLanded => *should_reset_lock_timer = true,
+                MinoState::AirBorne => *should_reset_lock_timer = false,
+                MinoState::Grounded => {}
+            }
+        }
+        let has_locked = line_clear.is_some();
+        if let Some(line_clear) = line_clear {
+            #[cfg(feature = "alloc")]
+            line_clears.push(line_clear);
+            #[cfg(not(feature = "alloc"))]
+            let _ = line_clears.push(line_clear);
+        }
+        //新しいミノが出現したらtimerをresetする
+        if has_locked || (is_hold && is_succeeded) {
+            self.gravity_timer = Duration::ZERO;
+            *should_reset_lock_timer = true;
+        }
+        is_succeeded
+    }
+    pub fn get_tetris_manager(&self) -> &TetrisManager<R> {
+        &self.tetris_manager
+    }
+    pub fn get_timing_config(&self) -> &TimingConfig {
+        &self.timing_config
+    }
+    pub fn get_input_state(&self) -> &InputState {
+        &self.input_state
+    }
+    pub fn get_lock_timer(&self) -> Duration {
+        self.lock_timer
+    }
+    pub fn is_game_over(&self) -> bool {
+        self.tetris_manager.get_game_over().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GarbageDelay, MinoType, TetrisConfig};
+
+    const TICK: Duration = Duration::from_millis(1);
+
+    //Tだけが出現するので出現位置は(3,19)
+    fn new_timed(tetris_config: TetrisConfig, timing_config: TimingConfig) -> TimedTetrisManager {
+        let sequence = [MinoType::MinoT; 8];
+        #[cfg(feature = "alloc")]
+        let tetris_manager =
+            TetrisManager::with_sequence(tetris_config, &sequence, None, None, &0, 10, 42);
+        #[cfg(not(feature = "alloc"))]
+        let tetris_manager = TetrisManager::with_sequence(tetris_config, &sequence, None, None, &0);
+        TimedTetrisManager::new(
+            tetris_manager.unwrap(),
+            TimingConfig {
+                tick: TICK,
+                ..timing_config
+            },
+        )
+    }
+    fn position(timed: &TimedTetrisManager) -> (i64, i64) {
+        let current_mino = &timed.get_tetris_manager().current_mino;
+        (current_mino.x, current_mino.y)
+    }
+    //1フレームずつ進めて各フレームの後の位置を返す
+    fn positions<const N: usize>(
+        timed: &mut TimedTetrisManager,
+        key_events: &[KeyEvent],
+    ) -> [(i64, i64); N] {
+        core::array::from_fn(|frame| {
+            let _ = timed.tick(if frame == 0 { key_events } else { &[] });
+            position(timed)
+        })
+    }
+
+    #[test]
+    fn gravity_accumulates_over_frames() {
+        let timing_config = TimingConfig {
+            gravity: TICK * 3,
+            ..TimingConfig::default()
+        };
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        assert_eq!(
+            positions(&mut timed, &[]),
+            [(3, 19), (3, 19), (3, 20), (3, 20), (3, 20), (3, 21)]
+        );
+        //1フレームに満たない時間は次のadvanceに持ち越される
+        let _ = timed.advance(TICK / 2, &[]);
+        let _ = timed.advance(TICK * 2, &[]);
+        assert_eq!(position(&timed), (3, 21));
+        let _ = timed.advance(TICK / 2, &[]);
+        assert_eq!(position(&timed), (3, 22));
+    }
+
+    #[test]
+    fn lock_delay_resets_on_move_until_limit() {
+        let timing_config = TimingConfig {
+            gravity: Duration::ZERO,
+            lock_delay: TICK * 5,
+            ..TimingConfig::default()
+        };
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        //接地したフレームから5フレーム後に固定される
+        for _ in 0..5 {
+            assert!(timed.tick(&[]).1.is_empty());
+        }
+        assert_eq!(timed.tick(&[]).1.len(), 1);
+
+        //接地中に移動するとlock delayが最初からになる
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        for _ in 0..3 {
+            let _ = timed.tick(&[]);
+        }
+        assert!(
+            timed
+                .tick(&[KeyEvent::Pressed(Key::Left), KeyEvent::Released(Key::Left)])
+                .1
+                .is_empty()
+        );
+        assert_eq!(timed.get_lock_timer(), Duration::ZERO);
+        for _ in 0..4 {
+            assert!(timed.tick(&[]).1.is_empty());
+        }
+        assert_eq!(timed.tick(&[]).1.len(), 1);
+
+        //move_reset_limitを超えて移動すると即座に固定される
+        let tetris_config = TetrisConfig {
+            move_reset_limit: Some(2),
+            ..TetrisConfig::default()
+        };
+        let mut timed = new_timed(tetris_config, timing_config);
+        let mut locked_frame = None;
+        for frame in 0..5 {
+            let key = if frame % 2 == 0 {
+                Key::Left
+            } else {
+                Key::Right
+            };
+            let key_events = [KeyEvent::Pressed(key), KeyEvent::Released(key)];
+            if !timed.tick(&key_events).1.is_empty() {
+                locked_frame = Some(frame);
+                break;
+            }
+        }
+        assert_eq!(locked_frame, Some(4));
+    }
+
+    #[test]
+    fn das_arr_and_dcd() {
+        let input_config = InputConfig {
+            das_frames: 3,
+            arr_frames: 2,
+            ..InputConfig::default()
+        };
+        let timing_config = TimingConfig {
+            input_config,
+            ..TimingConfig::default()
+        };
+        //押した瞬間に1マス、DASが溜まってからはARRごとに1マス移動して壁で止まる
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        let xs = positions::<8>(&mut timed, &[KeyEvent::Pressed(Key::Left)]).map(|(x, _)| x);
+        assert_eq!(xs, [2, 2, 2, 1, 1, 0, 0, 0]);
+
+        //ARRが0ならDASが溜まった瞬間に壁まで移動する
+        let timing_config = TimingConfig {
+            input_config: InputConfig {
+                arr_frames: 0,
+                ..input_config
+            },
+            ..TimingConfig::default()
+        };
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        let xs = positions::<4>(&mut timed, &[KeyEvent::Pressed(Key::Right)]).map(|(x, _)| x);
+        assert_eq!(xs, [4, 4, 4, 7]);
+
+        //回転するとDCDの間だけ自動移動が止まる
+        let timing_config = TimingConfig {
+            input_config: InputConfig {
+                arr_frames: 1,
+                dcd_frames: 2,
+                ..input_config
+            },
+            ..TimingConfig::default()
+        };
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        let xs = positions::<5>(&mut timed, &[KeyEvent::Pressed(Key::Right)]).map(|(x, _)| x);
+        assert_eq!(xs, [4, 4, 4, 5, 6]);
+        let _ = timed.tick(&[KeyEvent::Pressed(Key::RotateClockWise)]);
+        assert_eq!(position(&timed).0, 6);
+        let _ = timed.tick(&[]);
+        assert_eq!(position(&timed).0, 7);
+    }
+
+    #[test]
+    fn soft_drop_moves_every_soft_drop_frames() {
+        let timing_config = TimingConfig {
+            input_config: InputConfig {
+                soft_drop_frames: 2,
+                ..InputConfig::default()
+            },
+            ..TimingConfig::default()
+        };
+        let mut timed = new_timed(TetrisConfig::default(), timing_config);
+        let ys = positions::<5>(&mut timed, &[KeyEvent::Pressed(Key::SoftDrop)]).map(|(_, y)| y);
+        assert_eq!(ys, [20, 20, 21, 21, 22]);
+        let _ = timed.tick(&[KeyEvent::Released(Key::SoftDrop)]);
+        assert_eq!(position(&timed).1, 22);
+    }
+
+    #[test]
+    fn garbage_delay_elapses_every_frame() {
+        let tetris_config = TetrisConfig {
+            garbage_delay: GarbageDelay::Frames(3),
+            ..TetrisConfig::default()
+        };
+        let mut timed = new_timed(tetris_config, TimingConfig::default());
+        let attacked_line = crate::AttackedLine {
+            hole_indexes: None,
+            can_be_cleared: true,
+        };
+        assert!(
+            timed
+                .command(MovementCommand::Attacked(attacked_line))
+                .0
+                .is_ok()
+        );
+        let _ = timed.advance(TICK * 2, &[]);
+        assert_eq!(
+            timed
+                .get_tetris_manager()
+                .get_garbage_queue()
+                .get_ready_lines(),
+            0
+        );
+        let _ = timed.tick(&[]);
+        assert_eq!(
+            timed
+                .get_tetris_manager()
+                .get_garbage_queue()
+                .get_ready_lines(),
+            1
+        );
+    }
+}